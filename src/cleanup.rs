@@ -0,0 +1,633 @@
+//! Song housekeeping: removal of the elements unused by the arrangement
+//! or duplicated, and packing of the remaining ones.
+use crate::eq::Equ;
+use crate::fx::*;
+use crate::instruments::*;
+use crate::remapper::{
+    Remapper, EQ_TRACKING_COMMAND_NAMES, INSTRUMENT_TRACKING_COMMAND_NAMES,
+    TABLE_TRACKING_COMMAND_NAMES,
+};
+use crate::songs::*;
+use crate::version::Version;
+
+/// Slots emptied by [`Song::garbage_collect`] or [`Song::deduplicate`],
+/// by increasing index
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct FreedSlots {
+    pub chains: Vec<u8>,
+    pub phrases: Vec<u8>,
+    pub instruments: Vec<u8>,
+    pub tables: Vec<u8>,
+    pub eqs: Vec<u8>,
+}
+
+impl FreedSlots {
+    /// Total number of freed slots
+    pub fn count(&self) -> usize {
+        self.chains.len()
+            + self.phrases.len()
+            + self.instruments.len()
+            + self.tables.len()
+            + self.eqs.len()
+    }
+}
+
+/// Everything reachable from the song steps, in the order in
+/// which every element is first met.
+#[derive(Default)]
+pub(crate) struct SongUsage {
+    pub chains: Vec<u8>,
+    pub phrases: Vec<u8>,
+    pub instruments: Vec<u8>,
+    pub tables: Vec<u8>,
+    pub eqs: Vec<u8>,
+}
+
+impl SongUsage {
+    pub(crate) fn of(song: &Song) -> SongUsage {
+        let fx_commands = FX::fx_command_names(song.version);
+        let mut walk = UsageWalk {
+            song,
+            instrument_commands: fx_commands.find_indices(&INSTRUMENT_TRACKING_COMMAND_NAMES),
+            table_commands: fx_commands.find_indices(&TABLE_TRACKING_COMMAND_NAMES),
+            eq_commands: fx_commands.find_indices(&EQ_TRACKING_COMMAND_NAMES),
+            usage: SongUsage::default(),
+        };
+
+        // row by row, as the arrangement is read
+        for &chain in song.song.steps.iter() {
+            walk.visit_chain(chain);
+        }
+
+        walk.usage
+    }
+}
+
+struct UsageWalk<'a> {
+    song: &'a Song,
+    instrument_commands: Vec<u8>,
+    table_commands: Vec<u8>,
+    eq_commands: Vec<u8>,
+    usage: SongUsage,
+}
+
+impl UsageWalk<'_> {
+
+    /// Register an element, telling if it was met for the first time
+    fn first_visit(seen: &mut Vec<u8>, index: u8, count: usize) -> bool {
+        if (index as usize) >= count || seen.contains(&index) {
+            false
+        } else {
+            seen.push(index);
+            true
+        }
+    }
+
+    fn visit_chain(&mut self, chain: u8) {
+        if !Self::first_visit(&mut self.usage.chains, chain, Song::N_CHAINS) {
+            return;
+        }
+
+        for step in self.song.chains[chain as usize].steps.iter() {
+            self.visit_phrase(step.phrase);
+        }
+    }
+
+    fn visit_phrase(&mut self, phrase: u8) {
+        if !Self::first_visit(&mut self.usage.phrases, phrase, Song::N_PHRASES) {
+            return;
+        }
+
+        for step in self.song.phrases[phrase as usize].steps.iter() {
+            self.visit_instrument(step.instrument);
+            self.visit_fx(step.all_fx());
+        }
+    }
+
+    fn visit_instrument(&mut self, instrument: u8) {
+        let song = self.song;
+        let exists = song.instruments.get(instrument as usize).is_some_and(|i| !i.is_empty());
+        if !exists || !Self::first_visit(&mut self.usage.instruments, instrument, Song::N_INSTRUMENTS) {
+            return;
+        }
+
+        if let Some(eq) = song.instruments[instrument as usize].equ() {
+            self.visit_eq(eq);
+        }
+
+        // each instrument has its own table
+        self.visit_table(instrument);
+    }
+
+    fn visit_table(&mut self, table: u8) {
+        if !Self::first_visit(&mut self.usage.tables, table, Song::N_TABLES) {
+            return;
+        }
+
+        for step in self.song.tables[table as usize].steps.iter() {
+            self.visit_fx(step.all_fx());
+        }
+    }
+
+    fn visit_eq(&mut self, eq: u8) {
+        Self::first_visit(&mut self.usage.eqs, eq, self.song.eqs.len());
+    }
+
+    fn visit_fx(&mut self, fxs: [FX; 3]) {
+        for fx in fxs {
+            if self.instrument_commands.contains(&fx.command) {
+                self.visit_instrument(fx.value);
+            } else if self.table_commands.contains(&fx.command) {
+                self.visit_table(fx.value);
+            } else if self.eq_commands.contains(&fx.command) {
+                self.visit_eq(fx.value);
+            }
+        }
+    }
+}
+
+/// Empty every element not in `used` which had content, returning
+/// the cleared indices.
+fn clear_unused<T>(
+    elements: &mut [T],
+    used: &[u8],
+    is_empty: impl Fn(&T) -> bool,
+    empty: impl Fn() -> T,
+) -> Vec<u8> {
+    let mut freed = vec![];
+    for (i, element) in elements.iter_mut().enumerate() {
+        if !used.contains(&(i as u8)) && !is_empty(element) {
+            *element = empty();
+            freed.push(i as u8);
+        }
+    }
+    freed
+}
+
+/// Send the `used` indices of the range, in order, to the first slots
+/// of the range and the other indices to the remaining ones, so no two
+/// elements end up in the same slot.
+fn pack(mapping: &mut [u8], range: std::ops::Range<usize>, used: &[u8]) {
+    let order = used
+        .iter()
+        .map(|&i| i as usize)
+        .filter(|i| range.contains(i))
+        .chain(range.clone().filter(|i| !used.contains(&(*i as u8))));
+
+    for (slot, index) in range.clone().zip(order) {
+        mapping[index] = slot as u8;
+    }
+}
+
+/// Indices whose element has content and changes place
+fn moved<T>(elements: &[T], mapping: &[u8], is_empty: impl Fn(&T) -> bool) -> Vec<u8> {
+    (0..mapping.len())
+        .filter(|&i| mapping[i] as usize != i && !is_empty(&elements[i]))
+        .map(|i| i as u8)
+        .collect()
+}
+
+/// Point every candidate equal to a previous one to the first of them,
+/// returning the redirected indices.
+fn merge_duplicates<T: PartialEq>(candidates: &[(usize, T)], mapping: &mut [u8]) -> Vec<u8> {
+    let mut duplicates = vec![];
+    for (k, (index, element)) in candidates.iter().enumerate() {
+        if let Some((canonical, _)) = candidates[..k].iter().find(|(_, other)| other == element) {
+            mapping[*index] = *canonical as u8;
+            duplicates.push(*index as u8);
+        }
+    }
+    duplicates
+}
+
+/// Remapper leaving every element in place. The EQ mapping of
+/// [`Remapper::default_ver`] sends every EQ to 0, the allocation of
+/// copies filling it.
+pub(crate) fn identity_remapper(version: Version) -> Remapper {
+    let mut remapper = Remapper::default_ver(version);
+    for (i, eq) in remapper.eq_mapping.mapping.iter_mut().enumerate() {
+        *eq = i as u8;
+    }
+    remapper
+}
+
+/// Indices of the elements with content
+fn with_content<T>(elements: &[T], is_empty: impl Fn(&T) -> bool) -> Vec<u8> {
+    (0..elements.len())
+        .filter(|&i| !is_empty(&elements[i]))
+        .map(|i| i as u8)
+        .collect()
+}
+
+/// Apply a remapping of the song onto itself. Unlike
+/// [`Remapper::renumber`], every slot is freed before being filled,
+/// so elements can swap places, and the references of the elements
+/// left in place and of the song steps are rewritten: every element
+/// with content goes through [`Remapper::apply`], those not moved
+/// being mapped to their own slot.
+fn renumber_in_place(remapper: &Remapper, song: &mut Song) {
+    let from = song.clone();
+    let mut all = remapper.clone();
+
+    let eq_count = all.eq_mapping.mapping.len().min(from.eqs.len());
+    all.eq_mapping.to_move = with_content(&from.eqs[..eq_count], Equ::is_empty);
+    all.instrument_mapping.to_move = with_content(&from.instruments, Instrument::is_empty);
+    let instruments = &all.instrument_mapping.to_move;
+    all.table_mapping.to_move = with_content(&from.tables, Table::is_empty)
+        .into_iter()
+        .filter(|t| !instruments.contains(t))
+        .collect();
+    all.phrase_mapping.to_move = with_content(&from.phrases, Phrase::is_empty);
+    all.chain_mapping.to_move = with_content(&from.chains, Chain::is_empty);
+
+    // free the slots, apply fills back the used ones
+    for equ in all.eq_mapping.to_move.iter() {
+        song.eqs[*equ as usize].clear();
+    }
+
+    for instr_id in all.instrument_mapping.to_move.iter() {
+        song.instruments[*instr_id as usize] = Instrument::None;
+        song.tables[*instr_id as usize].clear();
+    }
+
+    for table_id in all.table_mapping.to_move.iter() {
+        song.tables[*table_id as usize].clear();
+    }
+
+    for phrase_id in all.phrase_mapping.to_move.iter() {
+        song.phrases[*phrase_id as usize].clear();
+    }
+
+    for chain_id in all.chain_mapping.to_move.iter() {
+        song.chains[*chain_id as usize].clear();
+    }
+
+    all.apply(&from, song);
+
+    for step in song.song.steps.iter_mut() {
+        if (*step as usize) < Song::N_CHAINS {
+            *step = all.out_chain(*step);
+        }
+    }
+}
+
+impl Song {
+    /// Clear every chain, phrase, instrument, table and EQ that can't be
+    /// reached from the song steps, following chains, phrases and the
+    /// INS/NXT, TBX/TBL and EQI/EQM effects of phrases and tables.
+    /// Slots that were already empty are not reported.
+    pub fn garbage_collect(&mut self) -> FreedSlots {
+        let usage = SongUsage::of(self);
+        let version = self.version;
+
+        FreedSlots {
+            chains: clear_unused(&mut self.chains, &usage.chains, Chain::is_empty, Chain::default),
+            phrases: clear_unused(&mut self.phrases, &usage.phrases, Phrase::is_empty, || {
+                Phrase::empty(version)
+            }),
+            instruments: clear_unused(
+                &mut self.instruments,
+                &usage.instruments,
+                Instrument::is_empty,
+                || Instrument::None,
+            ),
+            tables: clear_unused(&mut self.tables, &usage.tables, Table::is_empty, || {
+                Table::default_ver(version)
+            }),
+            eqs: clear_unused(&mut self.eqs, &usage.eqs, Equ::is_empty, Equ::empty),
+        }
+    }
+
+    /// Garbage collect the song, then renumber the chains, phrases,
+    /// instruments, tables and EQs still in use so they are packed from
+    /// index 0 in the order of their first appearance in the arrangement.
+    /// The tables of the instruments follow them, the other tables are
+    /// packed from 0x80. Every reference, song steps included, is
+    /// rewritten. Returns the applied remapping.
+    pub fn compact(&mut self) -> Remapper {
+        self.garbage_collect();
+        let usage = SongUsage::of(self);
+        let mut remapper = identity_remapper(self.version);
+
+        pack(&mut remapper.chain_mapping.mapping, 0..Song::N_CHAINS, &usage.chains);
+        pack(&mut remapper.phrase_mapping.mapping, 0..Song::N_PHRASES, &usage.phrases);
+        pack(&mut remapper.instrument_mapping.mapping, 0..Song::N_INSTRUMENTS, &usage.instruments);
+        let eq_count = remapper.eq_mapping.mapping.len().min(self.eqs.len());
+        pack(&mut remapper.eq_mapping.mapping, 0..eq_count, &usage.eqs);
+
+        // instrument tables stay with their instrument
+        let tables = &mut remapper.table_mapping.mapping;
+        tables[..Song::N_INSTRUMENTS].copy_from_slice(&remapper.instrument_mapping.mapping);
+        pack(tables, Song::N_INSTRUMENTS..Song::N_TABLES, &usage.tables);
+
+        remapper.chain_mapping.to_move =
+            moved(&self.chains, &remapper.chain_mapping.mapping, Chain::is_empty);
+        remapper.phrase_mapping.to_move =
+            moved(&self.phrases, &remapper.phrase_mapping.mapping, Phrase::is_empty);
+        remapper.instrument_mapping.to_move =
+            moved(&self.instruments, &remapper.instrument_mapping.mapping, Instrument::is_empty);
+        remapper.eq_mapping.to_move =
+            moved(&self.eqs, &remapper.eq_mapping.mapping[..eq_count], Equ::is_empty);
+
+        let moved_instruments = &remapper.instrument_mapping.to_move;
+        remapper.table_mapping.to_move =
+            moved(&self.tables, &remapper.table_mapping.mapping, Table::is_empty)
+                .into_iter()
+                .filter(|t| !moved_instruments.contains(t))
+                .collect();
+
+        renumber_in_place(&remapper, self);
+        remapper
+    }
+
+    /// Merge the identical EQs, tables, phrases and chains into the
+    /// first of them, rewriting the references to the removed copies.
+    /// Tables are compared once their EQ references are merged, phrases
+    /// once their table and EQ ones are and chains once their phrases
+    /// are. Tables owned by an instrument are left alone.
+    pub fn deduplicate(&mut self) -> FreedSlots {
+        let mut remapper = identity_remapper(self.version);
+
+        let eq_count = remapper.eq_mapping.mapping.len().min(self.eqs.len());
+        let eqs: Vec<_> = (0..eq_count)
+            .filter(|&i| !self.eqs[i].is_empty())
+            .map(|i| (i, self.eqs[i].clone()))
+            .collect();
+        remapper.eq_mapping.to_move = merge_duplicates(&eqs, &mut remapper.eq_mapping.mapping);
+
+        let tables: Vec<_> = (0..Song::N_TABLES)
+            .filter(|&i| !self.tables[i].is_empty())
+            .filter(|&i| i >= self.instruments.len() || self.instruments[i].is_empty())
+            .map(|i| {
+                let table = self.tables[i].map_instr(
+                    &remapper.instrument_mapping,
+                    &remapper.table_mapping,
+                    &remapper.eq_mapping,
+                );
+                (i, table)
+            })
+            .collect();
+        remapper.table_mapping.to_move =
+            merge_duplicates(&tables, &mut remapper.table_mapping.mapping);
+
+        let phrases: Vec<_> = (0..Song::N_PHRASES)
+            .filter(|&i| !self.phrases[i].is_empty())
+            .map(|i| {
+                let phrase = self.phrases[i].map_instruments(
+                    &remapper.instrument_mapping,
+                    &remapper.table_mapping,
+                    &remapper.eq_mapping,
+                );
+                (i, phrase)
+            })
+            .collect();
+        remapper.phrase_mapping.to_move =
+            merge_duplicates(&phrases, &mut remapper.phrase_mapping.mapping);
+
+        let chains: Vec<_> = (0..Song::N_CHAINS)
+            .filter(|&i| !self.chains[i].is_empty())
+            .map(|i| (i, self.chains[i].map(&remapper.phrase_mapping)))
+            .collect();
+        remapper.chain_mapping.to_move =
+            merge_duplicates(&chains, &mut remapper.chain_mapping.mapping);
+
+        // the copies being identical, moving them over the kept
+        // element only frees their slot
+        renumber_in_place(&remapper, self);
+
+        FreedSlots {
+            chains: remapper.chain_mapping.to_move,
+            phrases: remapper.phrase_mapping.to_move,
+            instruments: vec![],
+            tables: remapper.table_mapping.to_move,
+            eqs: remapper.eq_mapping.to_move,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cleanup::*;
+    use crate::test_utils::{fx, read_song};
+
+    #[test]
+    fn test_garbage_collect_follows_references() {
+        let mut song = read_song("./examples/songs/V6EMPTY.m8s");
+        let trackeq = read_song("./examples/songs/TRACKEQ.m8s");
+        for i in 0..4 {
+            song.instruments[i] = trackeq.instruments[1].clone();
+            song.instruments[i].set_eq(i as u8);
+            song.eqs[i] = trackeq.eqs[1].clone();
+        }
+
+        song.song.steps[8] = 0x10;
+        song.chains[0x10].steps[3].phrase = 0x20;
+        song.phrases[0x20].steps[0].instrument = 1;
+        song.phrases[0x20].steps[1].fx1 = fx(&song, "TBX", 0x90);
+        song.tables[0x90].steps[0].fx1 = fx(&song, "NXT", 2);
+
+        // unreachable
+        song.chains[0x11].steps[0].phrase = 0x21;
+        song.phrases[0x21].steps[0].instrument = 3;
+        song.tables[0x91].steps[0].fx1 = fx(&song, "NXT", 0);
+
+        let freed = song.garbage_collect();
+        assert_eq!(
+            freed,
+            FreedSlots {
+                chains: vec![0x11],
+                phrases: vec![0x21],
+                instruments: vec![0, 3],
+                tables: vec![0x91],
+                eqs: vec![0, 3],
+            }
+        );
+        assert_eq!(freed.count(), 7);
+
+        let with_eq = |eq| {
+            let mut instrument = trackeq.instruments[1].clone();
+            instrument.set_eq(eq);
+            instrument
+        };
+        assert!(song.instruments[0].is_empty());
+        assert_eq!(song.instruments[1], with_eq(1));
+        assert_eq!(song.instruments[2], with_eq(2));
+        assert!(song.phrases[0x21].is_empty());
+        assert!(!song.tables[0x90].is_empty());
+        assert!(song.eqs[3].is_empty());
+
+        assert_eq!(song.garbage_collect(), FreedSlots::default());
+    }
+
+    #[test]
+    fn test_garbage_collect_keeps_playback() {
+        for path in ["./examples/songs/Bundle/FDUB3.m8s", "./examples/songs/TRACKEQ.m8s"] {
+            let mut song = read_song(path);
+            let timeline = song.timeline();
+            assert!(song.garbage_collect().count() > 0);
+            assert!(song.timeline() == timeline);
+        }
+    }
+
+    #[test]
+    fn test_compact_packs_by_first_appearance() {
+        let mut song = read_song("./examples/songs/V6EMPTY.m8s");
+        let trackeq = read_song("./examples/songs/TRACKEQ.m8s");
+        song.instruments[9] = trackeq.instruments[1].clone();
+        song.instruments[9].set_eq(7);
+        song.eqs[7] = trackeq.eqs[1].clone();
+        song.instruments[2] = trackeq.instruments[2].clone();
+        song.instruments[2].set_eq(0xFF);
+
+        song.song.steps[0] = 0x30;
+        song.song.steps[8] = 0x10;
+        song.song.steps[9] = 0x30;
+        song.chains[0x30].steps[0].phrase = 0x40;
+        song.chains[0x10].steps[0].phrase = 0x05;
+        song.phrases[0x40].steps[0].instrument = 9;
+        song.phrases[0x40].steps[1].fx1 = fx(&song, "TBX", 0xA0);
+        song.phrases[0x05].steps[0].instrument = 2;
+        song.tables[9].steps[0].fx1 = fx(&song, "NXT", 2);
+        song.tables[0xA0].steps[0].fx1 = fx(&song, "EQI", 7);
+
+        let remapper = song.compact();
+        assert_eq!(remapper.out_chain(0x30), 0);
+        assert_eq!(remapper.out_chain(0x10), 1);
+        assert_eq!(&song.song.steps[..10], &[0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 1, 0]);
+        assert_eq!(song.chains[0].steps[0].phrase, 0);
+        assert_eq!(song.chains[1].steps[0].phrase, 1);
+        assert!(song.chains[0x30].is_empty() && song.phrases[0x40].is_empty());
+
+        assert_eq!(song.phrases[0].steps[0].instrument, 0);
+        assert_eq!(song.phrases[0].steps[1].fx1, fx(&song, "TBX", 0x80));
+        assert_eq!(song.phrases[1].steps[0].instrument, 1);
+        assert_eq!(song.tables[0].steps[0].fx1, fx(&song, "NXT", 1));
+        assert_eq!(song.tables[0x80].steps[0].fx1, fx(&song, "EQI", 0));
+        assert!(song.tables[9].is_empty() && song.tables[0xA0].is_empty());
+
+        assert_eq!(song.instruments[0].equ(), Some(0));
+        assert!(song.eqs[0] == trackeq.eqs[1]);
+        assert!(song.eqs[7].is_empty());
+        assert_eq!(song.instruments[1].equ(), Some(0xFF));
+        assert!(song.instruments[2].is_empty() && song.instruments[9].is_empty());
+
+        let usage = SongUsage::of(&song);
+        assert_eq!(usage.chains, vec![0, 1]);
+        assert_eq!(usage.phrases, vec![0, 1]);
+        assert_eq!(usage.instruments, vec![0, 1]);
+        assert_eq!(usage.eqs, vec![0]);
+    }
+
+    #[test]
+    fn test_compact_keeps_playback() {
+        for path in ["./examples/songs/Bundle/FDUB3.m8s", "./examples/songs/TRACKEQ.m8s"] {
+            let mut song = read_song(path);
+            let mut collected = song.clone();
+            collected.garbage_collect();
+            let before = collected.timeline();
+
+            let remapper = song.compact();
+            let after = song.timeline();
+            assert_eq!(before.events.len(), after.events.len());
+            for (b, a) in before.events.iter().zip(after.events.iter()) {
+                let instrument = match b.instrument as usize {
+                    i if i < Song::N_INSTRUMENTS => remapper.instrument_mapping.mapping[i],
+                    _ => b.instrument,
+                };
+                let map = |fx: FX| {
+                    fx.map_instr(
+                        &remapper.instrument_mapping,
+                        &remapper.table_mapping,
+                        &remapper.eq_mapping,
+                    )
+                };
+                assert!(a.position == b.position && a.note == b.note && a.velocity == b.velocity);
+                assert_eq!(a.instrument, instrument);
+                assert_eq!(a.fx, b.fx.map(map));
+            }
+
+            for (from, instrument) in collected.instruments.iter().enumerate() {
+                let to = remapper.instrument_mapping.mapping[from] as usize;
+                assert_eq!(song.instruments[to].is_empty(), instrument.is_empty());
+            }
+
+            let usage = SongUsage::of(&song);
+            let packed = |used: &[u8]| used.iter().enumerate().all(|(i, &u)| i == u as usize);
+            assert!(packed(&usage.chains) && packed(&usage.phrases) && packed(&usage.instruments));
+            assert!(packed(&usage.eqs));
+        }
+    }
+
+    #[test]
+    fn test_deduplicate_merges_copies() {
+        let mut song = read_song("./examples/songs/V6EMPTY.m8s");
+        let trackeq = read_song("./examples/songs/TRACKEQ.m8s");
+        song.eqs[4] = trackeq.eqs[1].clone();
+        song.eqs[5] = trackeq.eqs[1].clone();
+        song.instruments[9] = trackeq.instruments[1].clone();
+        song.instruments[9].set_eq(5);
+
+        song.tables[0x80].steps[0].fx1 = fx(&song, "EQI", 4);
+        song.tables[0x81].steps[0].fx1 = fx(&song, "EQI", 5);
+        for (phrase, table) in [(3, 0x80), (7, 0x81)] {
+            song.phrases[phrase].steps[0].instrument = 9;
+            song.phrases[phrase].steps[0].fx1 = fx(&song, "TBX", table);
+        }
+        song.chains[1].steps[0].phrase = 3;
+        song.chains[2].steps[0].phrase = 7;
+        song.chains[2].steps[1].phrase = 3;
+        song.chains[4].steps[0].phrase = 7;
+        song.chains[4].steps[1].phrase = 7;
+        song.song.steps[0] = 4;
+        song.song.steps[1] = 2;
+
+        let freed = song.deduplicate();
+        assert_eq!(
+            freed,
+            FreedSlots {
+                chains: vec![4],
+                phrases: vec![7],
+                instruments: vec![],
+                tables: vec![0x81],
+                eqs: vec![5],
+            }
+        );
+        assert_eq!(freed.count(), 4);
+
+        assert_eq!(&song.song.steps[..2], &[2, 2]);
+        assert!(song.chains[4].is_empty());
+        assert_eq!(song.chains[2].steps[0].phrase, 3);
+        assert!(song.phrases[7].is_empty());
+        assert_eq!(song.phrases[3].steps[0].fx1, fx(&song, "TBX", 0x80));
+        assert!(song.tables[0x81].is_empty());
+        assert_eq!(song.tables[0x80].steps[0].fx1, fx(&song, "EQI", 4));
+        assert!(song.eqs[5].is_empty());
+        assert_eq!(song.instruments[9].equ(), Some(4));
+
+        assert_eq!(song.deduplicate(), FreedSlots::default());
+    }
+
+    #[test]
+    fn test_deduplicate_keeps_playback() {
+        let mut song = read_song("./examples/songs/Bundle/FDUB3.m8s");
+        let before = song.timeline();
+
+        // play a copy of the first chain and of its phrases instead
+        let chain = song.song.steps[0] as usize;
+        let copy = song.chains.iter().position(|c| c.is_empty()).unwrap();
+        song.chains[copy] = song.chains[chain].clone();
+        for step in song.chains[copy].steps.iter_mut().filter(|s| !s.is_empty()) {
+            let free = song.phrases.iter().position(|p| p.is_empty()).unwrap();
+            song.phrases[free] = song.phrases[step.phrase as usize].clone();
+            step.phrase = free as u8;
+        }
+        song.song.steps[0] = copy as u8;
+        assert!(song.timeline() == before);
+
+        let freed = song.deduplicate();
+        assert_eq!(freed.chains, vec![copy as u8]);
+        assert!(!freed.phrases.is_empty());
+        assert_eq!(song.song.steps[0] as usize, chain);
+        assert!(song.timeline() == before);
+    }
+}
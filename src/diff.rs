@@ -0,0 +1,511 @@
+//! Structural comparison of two songs.
+use std::fmt;
+
+use crate::fx::*;
+use crate::param_gatherer::*;
+use crate::songs::*;
+use crate::version::*;
+
+/// Parameter whose value differs, named and rendered through
+/// [`Describable`]. Nested parameters are named with their scopes
+/// separated by '/', like "MOD1/ATTACK". `None` when the parameter only
+/// exists on one side.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParameterChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Column of a phrase or table step which changed
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ColumnChange {
+    Note { old: Note, new: Note },
+    /// Table steps only
+    Transpose { old: u8, new: u8 },
+    Velocity { old: u8, new: u8 },
+    Instrument { old: u8, new: u8 },
+    /// Effect column, from 0 to 2
+    Fx { column: usize, old: FX, new: FX },
+}
+
+/// One difference found by [`Song::diff`]
+#[derive(PartialEq, Debug, Clone)]
+pub enum SongChange {
+    Version { old: Version, new: Version },
+
+    /// Name, tempo, transposition, key or quantization
+    Header(ParameterChange),
+
+    /// Chain played by a song cell
+    SongCell { row: usize, track: usize, old: u8, new: u8 },
+
+    ChainStep { chain: usize, step: usize, old: ChainStep, new: ChainStep },
+    PhraseStep { phrase: usize, step: usize, columns: Vec<ColumnChange> },
+    TableStep { table: usize, step: usize, columns: Vec<ColumnChange> },
+
+    /// Instrument of another kind, its parameters are not compared
+    InstrumentKind { instrument: usize, old: String, new: String },
+    Instrument { instrument: usize, change: ParameterChange },
+
+    Mixer(ParameterChange),
+    Effects(ParameterChange),
+    Eq { eq: usize, change: ParameterChange },
+    Groove { groove: usize, change: ParameterChange },
+    Scale { scale: usize, change: ParameterChange },
+}
+
+/// Changes from a song to another, in song, chain, phrase, table,
+/// instrument, mixer, effects, EQ, groove and scale order.
+/// Displayed one change per line.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SongDiff {
+    /// Version of the original song, naming its effects
+    pub old_version: Version,
+    /// Version of the compared song, naming its effects
+    pub new_version: Version,
+    pub changes: Vec<SongChange>,
+}
+
+impl SongDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Gatherer flattening a description as a list of named values
+#[derive(Default)]
+struct ParameterList {
+    scope: String,
+    values: Vec<(String, String)>,
+}
+
+impl ParameterList {
+    fn of<F>(f: F) -> Vec<(String, String)>
+    where
+        F: FnOnce(ParameterList) -> ParameterList,
+    {
+        f(ParameterList::default()).values
+    }
+
+    fn push(mut self, name: &str, value: String) -> Self {
+        let entry = (format!("{}{}", self.scope, name), value);
+        // some parameters are described twice, like the instrument EQ
+        if !self.values.contains(&entry) {
+            self.values.push(entry);
+        }
+        self
+    }
+}
+
+impl ParameterGatherer for ParameterList {
+    fn hex(self, name: &str, val: u8) -> Self {
+        self.push(name, format!("{val:02X}"))
+    }
+
+    fn bool(self, name: &str, val: bool) -> Self {
+        self.push(name, (if val { "ON" } else { "OFF" }).to_string())
+    }
+
+    fn float(self, name: &str, val: f64) -> Self {
+        self.push(name, format!("{val:.2}"))
+    }
+
+    fn str(self, name: &str, val: &str) -> Self {
+        self.push(name, val.to_string())
+    }
+
+    fn enumeration(self, name: &str, hex: u8, val: &str) -> Self {
+        self.push(name, format!("{hex:02X} {val}"))
+    }
+
+    fn nest_f<F>(self, name: &str, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        let scope = self.scope;
+        let nested = f(ParameterList {
+            scope: format!("{scope}{name}/"),
+            values: self.values,
+        });
+
+        ParameterList { scope, values: nested.values }
+    }
+}
+
+/// Compare two parameter lists, matching the parameters by name
+fn diff_parameters(old: Vec<(String, String)>, new: Vec<(String, String)>) -> Vec<ParameterChange> {
+    let mut new: Vec<Option<(String, String)>> = new.into_iter().map(Some).collect();
+    let mut out = vec![];
+
+    for (name, old_value) in old {
+        let found = new
+            .iter_mut()
+            .find(|n| n.as_ref().is_some_and(|(n, _)| *n == name))
+            .and_then(|n| n.take());
+
+        match found {
+            Some((_, new_value)) if new_value == old_value => {}
+            Some((_, new_value)) => out.push(ParameterChange { name, old: Some(old_value), new: Some(new_value) }),
+            None => out.push(ParameterChange { name, old: Some(old_value), new: None }),
+        }
+    }
+
+    for (name, new_value) in new.into_iter().flatten() {
+        out.push(ParameterChange { name, old: None, new: Some(new_value) });
+    }
+
+    out
+}
+
+fn describe_diff<T: Describable>(old: &T, old_ver: Version, new: &T, new_ver: Version) -> Vec<ParameterChange> {
+    diff_parameters(
+        ParameterList::of(|pg| old.describe(pg, old_ver)),
+        ParameterList::of(|pg| new.describe(pg, new_ver)),
+    )
+}
+
+fn header_parameters(song: &Song) -> Vec<(String, String)> {
+    ParameterList::of(|pg| {
+        pg.str("NAME", &song.name)
+            .float("TEMPO", song.tempo as f64)
+            .hex("TRANSPOSE", song.transpose)
+            .hex("KEY", song.key)
+            .hex("QUANTIZE", song.quantize)
+    })
+}
+
+fn diff_fx(out: &mut Vec<ColumnChange>, old: [FX; 3], new: [FX; 3]) {
+    for (column, (old, new)) in old.into_iter().zip(new).enumerate() {
+        if old != new {
+            out.push(ColumnChange::Fx { column, old, new });
+        }
+    }
+}
+
+fn diff_phrase_step(old: &Step, new: &Step) -> Vec<ColumnChange> {
+    let mut out = vec![];
+    if old.note != new.note {
+        out.push(ColumnChange::Note { old: old.note, new: new.note });
+    }
+    if old.velocity != new.velocity {
+        out.push(ColumnChange::Velocity { old: old.velocity, new: new.velocity });
+    }
+    if old.instrument != new.instrument {
+        out.push(ColumnChange::Instrument { old: old.instrument, new: new.instrument });
+    }
+    diff_fx(&mut out, old.all_fx(), new.all_fx());
+    out
+}
+
+fn diff_table_step(old: &TableStep, new: &TableStep) -> Vec<ColumnChange> {
+    let mut out = vec![];
+    if old.transpose != new.transpose {
+        out.push(ColumnChange::Transpose { old: old.transpose, new: new.transpose });
+    }
+    if old.velocity != new.velocity {
+        out.push(ColumnChange::Velocity { old: old.velocity, new: new.velocity });
+    }
+    diff_fx(&mut out, old.all_fx(), new.all_fx());
+    out
+}
+
+fn instrument_kind(instr: &crate::Instrument, ver: Version) -> String {
+    let kind = ParameterList::of(|pg| describe_succint(instr, pg, ver));
+    kind.into_iter().next().map(|(_, k)| k).unwrap_or_default()
+}
+
+impl Song {
+    /// List what changed from this song to `other`: song cells, chain,
+    /// phrase and table steps, instrument parameters, mixer, effects,
+    /// EQs, grooves and scales.
+    pub fn diff(&self, other: &Song) -> SongDiff {
+        let (old_ver, new_ver) = (self.version, other.version);
+        let mut changes = vec![];
+
+        if old_ver != new_ver {
+            changes.push(SongChange::Version { old: old_ver, new: new_ver });
+        }
+
+        changes.extend(
+            diff_parameters(header_parameters(self), header_parameters(other))
+                .into_iter()
+                .map(SongChange::Header),
+        );
+
+        let cells = self.song.steps.iter().zip(other.song.steps.iter());
+        for (i, (&old, &new)) in cells.enumerate() {
+            if old != new {
+                let (row, track) = (i / SongSteps::TRACK_COUNT, i % SongSteps::TRACK_COUNT);
+                changes.push(SongChange::SongCell { row, track, old, new });
+            }
+        }
+
+        for (chain, (old, new)) in self.chains.iter().zip(other.chains.iter()).enumerate() {
+            for (step, (&old, &new)) in old.steps.iter().zip(new.steps.iter()).enumerate() {
+                if old != new {
+                    changes.push(SongChange::ChainStep { chain, step, old, new });
+                }
+            }
+        }
+
+        for (phrase, (old, new)) in self.phrases.iter().zip(other.phrases.iter()).enumerate() {
+            for (step, (old, new)) in old.steps.iter().zip(new.steps.iter()).enumerate() {
+                let columns = diff_phrase_step(old, new);
+                if !columns.is_empty() {
+                    changes.push(SongChange::PhraseStep { phrase, step, columns });
+                }
+            }
+        }
+
+        for (table, (old, new)) in self.tables.iter().zip(other.tables.iter()).enumerate() {
+            for (step, (old, new)) in old.steps.iter().zip(new.steps.iter()).enumerate() {
+                let columns = diff_table_step(old, new);
+                if !columns.is_empty() {
+                    changes.push(SongChange::TableStep { table, step, columns });
+                }
+            }
+        }
+
+        let instruments = self.instruments.iter().zip(other.instruments.iter());
+        for (instrument, (old, new)) in instruments.enumerate() {
+            let (old_kind, new_kind) = (instrument_kind(old, old_ver), instrument_kind(new, new_ver));
+            if old_kind != new_kind {
+                changes.push(SongChange::InstrumentKind { instrument, old: old_kind, new: new_kind });
+            } else {
+                changes.extend(
+                    describe_diff(old, old_ver, new, new_ver)
+                        .into_iter()
+                        .map(|change| SongChange::Instrument { instrument, change }),
+                );
+            }
+        }
+
+        changes.extend(
+            describe_diff(&self.mixer_settings, old_ver, &other.mixer_settings, new_ver)
+                .into_iter()
+                .map(SongChange::Mixer),
+        );
+        changes.extend(
+            describe_diff(&self.effects_settings, old_ver, &other.effects_settings, new_ver)
+                .into_iter()
+                .map(SongChange::Effects),
+        );
+
+        for (eq, (old, new)) in self.eqs.iter().zip(other.eqs.iter()).enumerate() {
+            changes.extend(
+                describe_diff(old, old_ver, new, new_ver)
+                    .into_iter()
+                    .map(|change| SongChange::Eq { eq, change }),
+            );
+        }
+
+        for (groove, (old, new)) in self.grooves.iter().zip(other.grooves.iter()).enumerate() {
+            changes.extend(
+                describe_diff(old, old_ver, new, new_ver)
+                    .into_iter()
+                    .map(|change| SongChange::Groove { groove, change }),
+            );
+        }
+
+        for (scale, (old, new)) in self.scales.iter().zip(other.scales.iter()).enumerate() {
+            changes.extend(
+                describe_diff(old, old_ver, new, new_ver)
+                    .into_iter()
+                    .map(|change| SongChange::Scale { scale, change }),
+            );
+        }
+
+        SongDiff { old_version: old_ver, new_version: new_ver, changes }
+    }
+}
+
+impl fmt::Display for ParameterChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let old = self.old.as_deref().unwrap_or("(none)");
+        let new = self.new.as_deref().unwrap_or("(none)");
+        write!(f, "{} {} -> {}", self.name, old, new)
+    }
+}
+
+struct ColumnDisplay<'a> {
+    old_commands: FxCommands,
+    new_commands: FxCommands,
+    columns: &'a [ColumnChange],
+}
+
+fn render_fx(commands: FxCommands, fx: FX) -> String {
+    if fx.is_empty() {
+        "---".to_string()
+    } else {
+        let command = commands.try_render(fx.command).unwrap_or("?");
+        format!("{}{:02X}", command, fx.value)
+    }
+}
+
+impl fmt::Display for ColumnDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+
+            match *column {
+                ColumnChange::Note { old, new } => write!(f, "N {} -> {}", old, new)?,
+                ColumnChange::Transpose { old, new } => write!(f, "N {:02X} -> {:02X}", old, new)?,
+                ColumnChange::Velocity { old, new } => write!(f, "V {:02X} -> {:02X}", old, new)?,
+                ColumnChange::Instrument { old, new } => write!(f, "I {:02X} -> {:02X}", old, new)?,
+                ColumnChange::Fx { column, old, new } => {
+                    let (old, new) = (render_fx(self.old_commands, old), render_fx(self.new_commands, new));
+                    write!(f, "FX{} {} -> {}", column + 1, old, new)?
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for SongDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let old_commands = FX::fx_command_names(self.old_version);
+        let new_commands = FX::fx_command_names(self.new_version);
+        let columns = |columns| ColumnDisplay { old_commands, new_commands, columns };
+
+        for change in &self.changes {
+            match change {
+                SongChange::Version { old, new } => writeln!(f, "version {} -> {}", old, new)?,
+                SongChange::Header(c) => writeln!(f, "song {}", c)?,
+                SongChange::SongCell { row, track, old, new } => {
+                    writeln!(f, "song {:02X}:{} {:02X} -> {:02X}", row, track + 1, old, new)?
+                }
+                SongChange::ChainStep { chain, step, old, new } => writeln!(
+                    f,
+                    "chain {:02X} step {:X} {:02X} {:02X} -> {:02X} {:02X}",
+                    chain, step, old.phrase, old.transpose, new.phrase, new.transpose
+                )?,
+                SongChange::PhraseStep { phrase, step, columns: c } => {
+                    writeln!(f, "phrase {:02X} step {:X} {}", phrase, step, columns(c))?
+                }
+                SongChange::TableStep { table, step, columns: c } => {
+                    writeln!(f, "table {:02X} step {:X} {}", table, step, columns(c))?
+                }
+                SongChange::InstrumentKind { instrument, old, new } => {
+                    writeln!(f, "instrument {:02X} {} -> {}", instrument, old, new)?
+                }
+                SongChange::Instrument { instrument, change } => {
+                    writeln!(f, "instrument {:02X} {}", instrument, change)?
+                }
+                SongChange::Mixer(c) => writeln!(f, "mixer {}", c)?,
+                SongChange::Effects(c) => writeln!(f, "effects {}", c)?,
+                SongChange::Eq { eq, change } => writeln!(f, "eq {:02X} {}", eq, change)?,
+                SongChange::Groove { groove, change } => {
+                    writeln!(f, "groove {:02X} {}", groove, change)?
+                }
+                SongChange::Scale { scale, change } => writeln!(f, "scale {:02X} {}", scale, change)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::*;
+    use crate::test_utils::{fx, read_song};
+
+    fn change(name: &str, old: &str, new: &str) -> ParameterChange {
+        ParameterChange {
+            name: name.to_string(),
+            old: Some(old.to_string()),
+            new: Some(new.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_identical_songs() {
+        for path in ["./examples/songs/V4EMPTY.m8s", "./examples/songs/Bundle/FDUB3.m8s"] {
+            let song = read_song(path);
+            assert!(song.diff(&song.clone()).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_song_diff() {
+        let trackeq = read_song("./examples/songs/TRACKEQ.m8s");
+        let mut old = read_song("./examples/songs/V6EMPTY.m8s");
+        old.instruments[2] = trackeq.instruments[2].clone();
+
+        let mut new = old.clone();
+        let del = FX::fx_command_names(new.version).find_indices(&["DEL"])[0];
+        new.tempo = 130.0;
+        new.song.steps[9] = 3;
+        new.chains[3].steps[0].phrase = 5;
+        new.phrases[5].steps[2].note = Note(0x30);
+        new.phrases[5].steps[2].fx2 = FX { command: del, value: 0x20 };
+        new.tables[0x80].steps[1].transpose = 0x0C;
+        new.instruments[1] = trackeq.instruments[1].clone();
+        new.instruments[2].set_eq(4);
+        new.mixer_settings.master_volume = 0x40;
+        new.grooves[0].steps[2] = 5;
+        new.scales[1].notes[3].semitones = 0.5;
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.changes,
+            vec![
+                SongChange::Header(change("TEMPO", "120.00", "130.00")),
+                SongChange::SongCell { row: 1, track: 1, old: 0xFF, new: 3 },
+                SongChange::ChainStep {
+                    chain: 3,
+                    step: 0,
+                    old: ChainStep { phrase: 0xFF, transpose: 0 },
+                    new: ChainStep { phrase: 5, transpose: 0 },
+                },
+                SongChange::PhraseStep {
+                    phrase: 5,
+                    step: 2,
+                    columns: vec![
+                        ColumnChange::Note { old: Note(0xFF), new: Note(0x30) },
+                        ColumnChange::Fx {
+                            column: 1,
+                            old: FX::default(),
+                            new: FX { command: del, value: 0x20 }
+                        },
+                    ],
+                },
+                SongChange::TableStep {
+                    table: 0x80,
+                    step: 1,
+                    columns: vec![ColumnChange::Transpose { old: 0, new: 0x0C }],
+                },
+                SongChange::InstrumentKind {
+                    instrument: 1,
+                    old: "NONE".to_string(),
+                    new: "WAVSYNTH".to_string(),
+                },
+                SongChange::Instrument { instrument: 2, change: change("WAVSYNTH/EQ", "01", "04") },
+                SongChange::Mixer(change("MASTER_VOL", "E0", "40")),
+                SongChange::Groove { groove: 0, change: change("STEP 2", "FF", "05") },
+                SongChange::Scale { scale: 1, change: change("D#/OFFSET", "0.00", "0.50") },
+            ]
+        );
+
+        let text = diff.to_string();
+        assert!(text.contains("phrase 05 step 2 N --- -> C-5, FX2 --- -> DEL20\n"));
+        assert!(text.contains("instrument 02 WAVSYNTH/EQ 01 -> 04\n"));
+    }
+
+    #[test]
+    fn test_diff_versions_fx() {
+        let mut old = read_song("./examples/songs/V4EMPTY.m8s");
+        let mut new = read_song("./examples/songs/V6_2EMPTY.m8s");
+        old.phrases[0].steps[0].fx1 = fx(&old, "TPO", 0x01);
+        new.phrases[0].steps[0].fx1 = fx(&new, "OTT", 0x02);
+
+        let diff = old.diff(&new);
+        assert_eq!((diff.old_version, diff.new_version), (old.version, new.version));
+        assert!(diff.to_string().contains("phrase 00 step 0 FX1 TPO01 -> OTT02\n"));
+    }
+}
@@ -161,22 +161,22 @@ impl EqBand {
         w.write(self.q);
     }
 
-    pub fn from_reader(reader: &mut Reader) -> EqBand {
-        let mode = EqModeType(reader.read());
-        let freq_fin = reader.read();
-        let freq = reader.read();
-        let level_fin = reader.read();
-        let level = reader.read();
-        let q = reader.read();
-
-        Self {
+    pub fn from_reader(reader: &mut Reader) -> M8Result<EqBand> {
+        let mode = EqModeType(reader.read()?);
+        let freq_fin = reader.read()?;
+        let freq = reader.read()?;
+        let level_fin = reader.read()?;
+        let level = reader.read()?;
+        let q = reader.read()?;
+
+        Ok(Self {
             level,
             level_fin,
             freq,
             freq_fin,
             mode,
             q,
-        }
+        })
     }
 }
 
@@ -190,6 +190,15 @@ pub struct Equ {
 impl Equ {
     pub const V4_SIZE: usize = 3 * EqBand::V4_SIZE;
 
+    /// Flat EQ, as found in unused slots
+    pub fn empty() -> Equ {
+        Equ {
+            low: EqBand::default_low(),
+            mid: EqBand::default_mid(),
+            high: EqBand::default_high(),
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.low == EqBand::default_low()
             && self.mid == EqBand::default_mid()
@@ -208,10 +217,10 @@ impl Equ {
         self.high.write(w);
     }
 
-    pub fn from_reader(reader: &mut Reader) -> Equ {
-        let low = EqBand::from_reader(reader);
-        let mid = EqBand::from_reader(reader);
-        let high = EqBand::from_reader(reader);
-        Self { low, mid, high }
+    pub fn from_reader(reader: &mut Reader) -> M8Result<Equ> {
+        let low = EqBand::from_reader(reader)?;
+        let mid = EqBand::from_reader(reader)?;
+        let high = EqBand::from_reader(reader)?;
+        Ok(Self { low, mid, high })
     }
 }
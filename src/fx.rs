@@ -2,7 +2,7 @@ use crate::{reader::*, ReferenceTemplating};
 use crate::remapper::{EqMapping, InstrumentMapping, TableMapping};
 use crate::version::*;
 use crate::writer::Writer;
-use crate::CommandPack;
+use crate::{CommandPack, Instrument};
 use array_concat::*;
 
 #[derive(Copy, Clone)]
@@ -358,13 +358,168 @@ const COMMANDS_V4: [&'static str; concat_arrays_size!(SEQ_COMMAND_V3, FX_MIXER_C
 const COMMANDS_V6_2: [&'static str; concat_arrays_size!(SEQ_COMMAND_V3, FX_MIXER_COMMAND_V6_2)] =
     concat_arrays!(SEQ_COMMAND_V3, FX_MIXER_COMMAND_V6_2);
 
+/// Commands renamed between firmware versions, old name first.
+#[rustfmt::skip] // Keep constants with important order vertical for maintenance
+const RENAMED_COMMANDS : [(&str, &str); 14] = [
+    // 3.0
+    ("RAN", "RND"),
+    ("PSN", "PBN"),
+
+    // 4.0
+    ("VCD", "VDE"),
+    ("DJF", "DJC"),
+    ("IVO", "VIN"),
+    ("IV2", "VI2"),
+
+    // 6.2
+    ("XCM", "XMM"),
+    ("XCF", "XMF"),
+    ("XCW", "XMW"),
+    ("XCR", "XMR"),
+    ("VCH", "VMX"),
+    ("ICH", "IMX"),
+    ("IC2", "IM2"),
+    ("SCH", "SMX"),
+];
+
+/// Song versions changing the instrument specific command tables
+const INSTRUMENT_COMMAND_CHANGES : [Version; 2] = [
+    FIRMWARE_6_0_SONG_VERSION,
+    FIRMWARE_6_2_SONG_VERSION,
+];
+
+/// Most recent name of a command
+fn current_command_name(name: &str) -> &str {
+    RENAMED_COMMANDS
+        .iter()
+        .find(|(old, _)| *old == name)
+        .map_or(name, |(_, new)| new)
+}
+
+/// Renumbering of the effect commands between the command tables
+/// of two firmware versions.
+///
+/// Sequencer and mixer commands are renumbered by [`Self::translate`],
+/// instrument specific commands (from 0x80) depend on the instrument
+/// playing them and are renumbered by [`Self::translate_instr`].
+#[derive(Clone)]
+pub struct FxTranslation {
+    pub from: Version,
+    pub to: Version,
+
+    from_commands: FxCommands,
+
+    /// Command id in the target version for every command of the
+    /// source version, if it exists.
+    mapping: Vec<Option<u8>>,
+}
+
+impl FxTranslation {
+    pub fn new(from: Version, to: Version) -> Self {
+        let from_commands = FX::fx_command_names(from);
+        let to_commands = FX::fx_command_names(to);
+
+        let mapping = from_commands
+            .commands
+            .iter()
+            .map(|name| {
+                let name = current_command_name(name);
+                to_commands
+                    .commands
+                    .iter()
+                    .position(|to_name| current_command_name(to_name) == name)
+                    .map(|ix| ix as u8)
+            })
+            .collect();
+
+        Self { from, to, from_commands, mapping }
+    }
+
+    /// Effect renumbered for the target version, None if the
+    /// command does not exist in the target version.
+    pub fn translate(&self, fx: FX) -> Option<FX> {
+        match self.mapping.get(fx.command as usize) {
+            Some(Some(command)) => Some(FX { command: *command, value: fx.value }),
+            Some(None) => None,
+            // empty, instrument or unknown command
+            None => Some(fx),
+        }
+    }
+
+    /// Are the instrument specific commands numbered the same way in
+    /// both versions, whatever the instrument.
+    pub fn same_instrument_commands(&self) -> bool {
+        INSTRUMENT_COMMAND_CHANGES
+            .iter()
+            .all(|v| self.from.after(v) == self.to.after(v))
+    }
+
+    /// Effect renumbered for the target version, instrument specific
+    /// commands are renumbered with the command tables of `instrument`
+    /// in both versions. When the instrument or the command is unknown,
+    /// they are only kept if the instrument command tables didn't change
+    /// between the versions.
+    pub fn translate_instr(&self, fx: FX, instrument: Option<&Instrument>) -> Option<FX> {
+        if fx.is_empty() || (fx.command as usize) < CommandPack::INSTRUMENT_COMMAND_OFFSET {
+            return self.translate(fx);
+        }
+
+        let from_name = instrument.and_then(|i| i.instr_command_text(self.from).try_render(fx.command));
+        let (Some(instrument), Some(from_name)) = (instrument, from_name) else {
+            return self.same_instrument_commands().then_some(fx);
+        };
+
+        let to_pack = instrument.instr_command_text(self.to);
+        let name = current_command_name(from_name);
+        let same_name = |command: u8| {
+            to_pack
+                .try_render(command)
+                .is_some_and(|to_name| current_command_name(to_name) == name)
+        };
+
+        let command = if same_name(fx.command) {
+            fx.command
+        } else {
+            (CommandPack::INSTRUMENT_COMMAND_OFFSET as u8..0xFF).find(|c| same_name(*c))?
+        };
+
+        Some(FX { command, value: fx.value })
+    }
+
+    /// Same as translate, removing effects without equivalent
+    pub fn translate_or_clear(&self, fx: FX) -> FX {
+        self.translate(fx).unwrap_or_default()
+    }
+
+    /// Same as translate_instr, removing effects without equivalent
+    pub fn translate_instr_or_clear(&self, fx: FX, instrument: Option<&Instrument>) -> FX {
+        self.translate_instr(fx, instrument).unwrap_or_default()
+    }
+
+    /// Name of the command in the source version
+    pub fn command_name(&self, fx: FX) -> Option<&'static str> {
+        self.from_commands.try_render(fx.command)
+    }
+
+    /// Name of the command in the source version, instrument specific
+    /// commands are named after the instrument playing them.
+    pub fn instr_command_name(&self, fx: FX, instrument: Option<&Instrument>) -> Option<&'static str> {
+        match instrument {
+            Some(instrument) if (fx.command as usize) >= CommandPack::INSTRUMENT_COMMAND_OFFSET => {
+                instrument.instr_command_text(self.from).try_render(fx.command)
+            }
+            _ => self.command_name(fx),
+        }
+    }
+}
+
 impl FX {
     pub const V4_SIZE: usize = 2;
 
     pub(crate) fn from_reader(reader: &mut Reader) -> M8Result<Self> {
         Ok(Self {
-            command: reader.read(),
-            value: reader.read(),
+            command: reader.read()?,
+            value: reader.read()?,
         })
     }
 
@@ -389,6 +544,14 @@ impl FX {
         }
     }
 
+    /// Renumber the command for a song of another firmware version,
+    /// None if the command has no equivalent in the target version.
+    ///
+    /// Use [`FxTranslation`] to translate many effects.
+    pub fn translate(self, from: Version, to: Version) -> Option<FX> {
+        FxTranslation::new(from, to).translate(self)
+    }
+
     /// Retrieve command names for a given version
     pub fn fx_command_names(ver: Version) -> FxCommands {
         if ver.after(&FIRMWARE_6_2_SONG_VERSION) {
@@ -68,14 +68,15 @@ impl fmt::Debug for LimitType {
     }
 }
 
+/// Rejects values without a known limit type, giving back the value
 impl TryFrom<u8> for LimitType {
-    type Error = ParseError;
+    type Error = u8;
 
     fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
         if (value as usize) < LIMIT_TYPE.len() {
             Ok(LimitType(value))
         } else {
-            Err(ParseError(format!("Invalid fm wave {}", value)))
+            Err(value)
         }
     }
 }
@@ -148,7 +149,7 @@ impl SynthParams {
             filter_res: 0,
 
             amp: 0,
-            limit: LimitType::try_from(0)?,
+            limit: LimitType(0),
 
             shape: 0,
             mixer_pan: 0,
@@ -165,7 +166,7 @@ impl SynthParams {
     pub fn mod_only3(reader: &mut Reader, mod_offset: usize) -> M8Result<Self> {
         reader.set_pos(reader.pos() + mod_offset);
 
-        let mods = arr![Mod::from_reader(reader)?; 4];
+        let mods = Mod::read_mods(reader)?;
 
         Ok(Self {
             volume: 0,
@@ -177,7 +178,7 @@ impl SynthParams {
             filter_res: 0,
 
             amp: 0,
-            limit: LimitType::try_from(0)?,
+            limit: LimitType(0),
 
             shape: 0,
             mixer_pan: 0,
@@ -202,27 +203,27 @@ impl SynthParams {
             pitch,
             fine_tune,
 
-            filter_type: reader.read(),
-            filter_cutoff: reader.read(),
-            filter_res: reader.read(),
+            filter_type: reader.read()?,
+            filter_cutoff: reader.read()?,
+            filter_res: reader.read()?,
 
-            amp: reader.read(),
-            limit: LimitType::try_from(reader.read())?,
+            amp: reader.read()?,
+            limit: reader.read_value("limit type")?,
 
             shape: 0,
-            mixer_pan: reader.read(),
-            mixer_dry: reader.read(),
-            mixer_mfx: reader.read(),
-            mixer_delay: reader.read(),
-            mixer_reverb: reader.read(),
+            mixer_pan: reader.read()?,
+            mixer_dry: reader.read()?,
+            mixer_mfx: reader.read()?,
+            mixer_delay: reader.read()?,
+            mixer_reverb: reader.read()?,
 
             associated_eq: 0xFF,
 
             mods: [
-                AHDEnv::from_reader2(reader)?.to_mod(),
-                AHDEnv::from_reader2(reader)?.to_mod(),
-                LFO::from_reader2(reader)?.to_mod(),
-                LFO::from_reader2(reader)?.to_mod(),
+                in_modulator(0, AHDEnv::from_reader2(reader))?.to_mod(),
+                in_modulator(1, AHDEnv::from_reader2(reader))?.to_mod(),
+                in_modulator(2, LFO::from_reader2(reader))?.to_mod(),
+                in_modulator(3, LFO::from_reader2(reader))?.to_mod(),
             ],
         })
     }
@@ -274,28 +275,28 @@ impl SynthParams {
         eq: u8,
         mod_offset: usize,
     ) -> M8Result<Self> {
-        let filter_type = reader.read();
-        let filter_cutoff = reader.read();
-        let filter_res = reader.read();
+        let filter_type = reader.read()?;
+        let filter_cutoff = reader.read()?;
+        let filter_res = reader.read()?;
 
-        let amp = reader.read();
-        let limit = reader.read();
+        let amp = reader.read()?;
+        let limit = reader.read_value("limit type")?;
 
-        let mixer_pan = reader.read();
-        let mixer_dry = reader.read();
-        let mixer_chorus = reader.read();
-        let mixer_delay = reader.read();
-        let mixer_reverb = reader.read();
+        let mixer_pan = reader.read()?;
+        let mixer_dry = reader.read()?;
+        let mixer_chorus = reader.read()?;
+        let mixer_delay = reader.read()?;
+        let mixer_reverb = reader.read()?;
 
         let reader_pos = reader.pos();
 
         // I'll probably pay for it later
         reader.skip(3);
-        let shape = reader.read();
+        let shape = reader.read()?;
 
         let associated_eq = if version.after(&FIRMWARE_5_0_SONG_VERSION) {
             reader.set_pos(reader_pos + mod_offset - 1);
-            reader.read()
+            reader.read()?
         } else if version.after(&FIRMWARE_4_0_SONG_VERSION) {
             eq
         } else {
@@ -304,7 +305,7 @@ impl SynthParams {
 
         reader.set_pos(reader_pos + mod_offset);
 
-        let mods = arr![Mod::from_reader(reader)?; 4];
+        let mods = Mod::read_mods(reader)?;
 
         Ok(Self {
             volume,
@@ -316,7 +317,7 @@ impl SynthParams {
             filter_res,
 
             amp,
-            limit: LimitType::try_from(limit)?,
+            limit,
 
             mixer_pan,
             mixer_dry,
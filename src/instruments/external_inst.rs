@@ -130,19 +130,19 @@ impl ExternalInst {
     }
 
     pub fn from_reader(ver: Version, reader: &mut Reader, number: u8) -> M8Result<Self> {
-        let name = reader.read_string(12);
-        let transp_eq = TranspEq::from_version(ver, reader.read());
-
-        let table_tick = reader.read();
-        let volume = reader.read();
-        let pitch = reader.read();
-        let fine_tune = reader.read();
-
-        let input = reader.read();
-        let port = reader.read();
-        let channel = reader.read();
-        let bank = reader.read();
-        let program = reader.read();
+        let name = reader.read_string(12)?;
+        let transp_eq = TranspEq::from_version(ver, reader.read()?);
+
+        let table_tick = reader.read()?;
+        let volume = reader.read()?;
+        let pitch = reader.read()?;
+        let fine_tune = reader.read()?;
+
+        let input = reader.read()?;
+        let port = reader.read()?;
+        let channel = reader.read()?;
+        let bank = reader.read()?;
+        let program = reader.read()?;
         let cca = ControlChange::from_reader(reader)?;
         let ccb = ControlChange::from_reader(reader)?;
         let ccc = ControlChange::from_reader(reader)?;
@@ -31,14 +31,15 @@ const FM_ALGO_STRINGS: [&str; 0x0C] = [
     "A+B+C+D",
 ];
 
+/// Rejects values without a known algorithm, giving back the value
 impl TryFrom<u8> for FmAlgo {
-    type Error = ParseError;
+    type Error = u8;
 
     fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
         if (value as usize) < FM_ALGO_STRINGS.len() {
             Ok(FmAlgo(value))
         } else {
-            Err(ParseError(format!("Invalid fm algo {}", value)))
+            Err(value)
         }
     }
 }
@@ -290,40 +291,38 @@ impl FMSynth {
         number: u8,
         version: Version,
     ) -> M8Result<Self> {
-        let name = reader.read_string(12);
-        let transp_eq = TranspEq::from_version(ver, reader.read());
-        let table_tick = reader.read();
-        let volume = reader.read();
-        let pitch = reader.read();
-        let fine_tune = reader.read();
-
-        let algo = reader.read();
+        let name = reader.read_string(12)?;
+        let transp_eq = TranspEq::from_version(ver, reader.read()?);
+        let table_tick = reader.read()?;
+        let volume = reader.read()?;
+        let pitch = reader.read()?;
+        let fine_tune = reader.read()?;
+
+        let algo = reader.read_value("FM algo")?;
         let mut operators: [Operator; 4] = arr![Operator::default(); 4];
         if version.at_least(1, 4) {
             for i in 0..4 {
-                let wav_code = reader.read();
-                operators[i].shape = FMWave::try_from(wav_code)
-                    .map_err(|_| ParseError(format!("Invalid fm wave {}", wav_code)))?;
+                operators[i].shape = reader.read_value("FM wave")?;
             }
         }
         for i in 0..4 {
-            operators[i].ratio = reader.read();
-            operators[i].ratio_fine = reader.read();
+            operators[i].ratio = reader.read()?;
+            operators[i].ratio_fine = reader.read()?;
         }
         for i in 0..4 {
-            operators[i].level = reader.read();
-            operators[i].feedback = reader.read();
+            operators[i].level = reader.read()?;
+            operators[i].feedback = reader.read()?;
         }
         for i in 0..4 {
-            operators[i].mod_a = reader.read();
+            operators[i].mod_a = reader.read()?;
         }
         for i in 0..4 {
-            operators[i].mod_b = reader.read();
+            operators[i].mod_b = reader.read()?;
         }
-        let mod1 = reader.read();
-        let mod2 = reader.read();
-        let mod3 = reader.read();
-        let mod4 = reader.read();
+        let mod1 = reader.read()?;
+        let mod2 = reader.read()?;
+        let mod3 = reader.read()?;
+        let mod4 = reader.read()?;
 
         let synth_params = if version.after(&FIRMWARE_3_0_SONG_VERSION) {
             SynthParams::from_reader3(
@@ -346,7 +345,7 @@ impl FMSynth {
             table_tick,
             synth_params,
 
-            algo: FmAlgo(algo),
+            algo,
             operators,
             mod1,
             mod2,
@@ -22,13 +22,13 @@ pub struct Chord {
 }
 
 impl Chord {
-    pub fn read(reader: &mut Reader) -> Self{
-        let mask = reader.read();
+    pub fn read(reader: &mut Reader) -> M8Result<Self> {
+        let mask = reader.read()?;
 
-        Self {
+        Ok(Self {
             mask,
-            offsets: arr![reader.read(); 6]
-        }
+            offsets: arr![reader.read()?; 6]
+        })
     }
 
     pub fn write(&self, w: &mut Writer) {
@@ -213,19 +213,20 @@ impl HyperSynth {
     }
 
     pub fn from_reader(ver: Version, reader: &mut Reader, number: u8) -> M8Result<Self> {
-        let name = reader.read_string(12);
-        let transp_eq = TranspEq::from_version(ver, reader.read());
-        let table_tick = reader.read();
-        let volume = reader.read();
-        let pitch = reader.read();
-        let fine_tune = reader.read();
-
-        let default_chord = arr![reader.read(); 7];
-        let scale = reader.read();
-        let shift = reader.read();
-        let swarm = reader.read();
-        let width = reader.read();
-        let subosc = reader.read();
+        let name = reader.read_string(12)?;
+        let transp_eq = TranspEq::from_version(ver, reader.read()?);
+        let table_tick = reader.read()?;
+        let volume = reader.read()?;
+        let pitch = reader.read()?;
+        let fine_tune = reader.read()?;
+
+        let default_chord = arr![reader.read()?; 7];
+        let scale = reader.read()?;
+        let shift = reader.read()?;
+        let swarm = reader.read()?;
+        let width = reader.read()?;
+        let subosc = reader.read()?;
+        let params_pos = reader.pos();
         let synth_params = SynthParams::from_reader3(
             ver,
             reader,
@@ -236,14 +237,16 @@ impl HyperSynth {
             HyperSynth::MOD_OFFSET,
         )?;
 
-        let chords = arr![Chord::read(reader); 0x10];
+        let chords = arr![Chord::read(reader)?; 0x10];
 
         let shape =
             if ver.at_least(6, 6) {
                 let shape_byte = synth_params.shape;
-                let hsshape =
-                    HyperSynthShape::try_from(shape_byte)
-                        .map_err(|_| ParseError(String::from("Invalid hypersynth shape parameter")))?;
+                // the shape is read with the synth parameters, after
+                // 10 bytes of filter, amp and mixer and 3 unknown ones
+                let shape_offset = params_pos + 13;
+                let hsshape = HyperSynthShape::try_from(shape_byte)
+                    .map_err(|_| reader.invalid_at(shape_offset, "hypersynth shape", shape_byte))?;
 
                 Some(hsshape)
             } else {
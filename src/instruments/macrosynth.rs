@@ -180,21 +180,19 @@ impl MacroSynth {
         number: u8,
         version: Version,
     ) -> M8Result<Self> {
-        let ms_pos = reader.pos();
-        let name = reader.read_string(12);
-
-        let transp_eq = TranspEq::from_version(ver, reader.read());
-        let table_tick = reader.read();
-        let volume = reader.read();
-        let pitch = reader.read();
-        let fine_tune = reader.read();
-
-        let ofs_shape = reader.pos();
-        let shape = reader.read();
-        let timbre = reader.read();
-        let color = reader.read();
-        let degrade = reader.read();
-        let redux = reader.read();
+        let name = reader.read_string(12)?;
+
+        let transp_eq = TranspEq::from_version(ver, reader.read()?);
+        let table_tick = reader.read()?;
+        let volume = reader.read()?;
+        let pitch = reader.read()?;
+        let fine_tune = reader.read()?;
+
+        let shape = reader.read_value("macrosynth shape")?;
+        let timbre = reader.read()?;
+        let color = reader.read()?;
+        let degrade = reader.read()?;
+        let redux = reader.read()?;
 
         let synth_params = if version.after(&FIRMWARE_3_0_SONG_VERSION) {
             SynthParams::from_reader3(
@@ -210,7 +208,6 @@ impl MacroSynth {
             SynthParams::from_reader2(reader, volume, pitch, fine_tune)?
         };
 
-        let nc = name.clone();
         Ok(MacroSynth {
             number,
             name,
@@ -218,11 +215,7 @@ impl MacroSynth {
             table_tick,
             synth_params,
 
-            shape: shape.try_into().map_err(|_| {
-                ParseError(format!(
-                    "I{number:X} Wrong macrosynth@{ms_pos} ({nc}) shape {shape}@0x{ofs_shape}"
-                ))
-            })?,
+            shape,
             timbre,
             color,
             degrade,
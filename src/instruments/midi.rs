@@ -26,8 +26,8 @@ impl ControlChange {
 
     pub fn from_reader(reader: &mut Reader) -> M8Result<Self> {
         Ok(Self {
-            number: reader.read(),
-            value: reader.read(),
+            number: reader.read()?,
+            value: reader.read()?,
         })
     }
 }
@@ -114,9 +114,9 @@ impl MIDIOut {
         PORTS[self.port as usize]
     }
 
-    pub fn write(&self, _ver: Version, w: &mut Writer) {
+    pub fn write(&self, ver: Version, w: &mut Writer) {
         w.write_string(&self.name, 12);
-        w.write(if self.transpose { 1 } else { 0 });
+        w.write(TranspEq::from(ver, self.transpose, self.mods.associated_eq).into());
         w.write(self.table_tick);
         w.write(self.port);
         w.write(self.channel);
@@ -138,26 +138,32 @@ impl MIDIOut {
         number: u8,
         version: Version,
     ) -> M8Result<Self> {
-        let name = reader.read_string(12);
-        let transpose = reader.read_bool();
-        let table_tick = reader.read();
-
-        let port = reader.read();
-        let channel = reader.read();
-        let bank_select = reader.read();
-        let program_change = reader.read();
-        reader.read_bytes(3); // discard
+        let name = reader.read_string(12)?;
+        let transp_eq = TranspEq::from_version(version, reader.read()?);
+        let table_tick = reader.read()?;
+
+        let port = reader.read()?;
+        let channel = reader.read()?;
+        let bank_select = reader.read()?;
+        let program_change = reader.read()?;
+        reader.read_bytes(3)?; // discard
         let custom_cc = arr![ControlChange::from_reader(reader)?; 10];
-        let mods = if version.after(&FIRMWARE_3_0_SONG_VERSION) {
+        let mut mods = if version.after(&FIRMWARE_3_0_SONG_VERSION) {
             SynthParams::mod_only3(reader, MIDIOut::MOD_OFFSET)?
         } else {
             SynthParams::mod_only2(reader)?
         };
 
+        // MIDI out has no EQ, but the 4.0 format still pack one
+        // alongside the transpose flag, keep it for rewriting.
+        if version.after(&FIRMWARE_4_0_SONG_VERSION) && !version.after(&FIRMWARE_5_0_SONG_VERSION) {
+            mods.associated_eq = transp_eq.eq;
+        }
+
         Ok(MIDIOut {
             number,
             name,
-            transpose,
+            transpose: transp_eq.transpose,
             table_tick,
 
             port,
@@ -338,11 +338,7 @@ impl Instrument {
     /// Read an in-memory instrument file along with its optional eq
     pub fn read_from_reader(reader: &mut Reader) -> M8Result<InstrumentWithEq> {
         let instrument_end_offset = Instrument::INSTRUMENT_MEMORY_SIZE + Version::SIZE;
-        if reader.len() < instrument_end_offset {
-            return Err(ParseError(
-                "File is not long enough to be a M8 Instrument".to_string(),
-            ));
-        }
+        reader.expect_len(instrument_end_offset)?;
 
         let version = Version::from_reader(reader)?;
         let instrument = Self::from_reader(reader, 0, version)?;
@@ -353,7 +349,7 @@ impl Instrument {
             Some(ofs) if version.after(&FIRMWARE_4_0_SONG_VERSION) => {
                 if reader.len() >= ofs + Equ::V4_SIZE {
                     reader.set_pos(ofs);
-                    Some(Equ::from_reader(reader))
+                    Some(Equ::from_reader(reader)?)
                 } else {
                     None
                 }
@@ -366,16 +362,19 @@ impl Instrument {
 
     /// Read a M8 instrument file along with its optional Eq definition.
     pub fn read(reader: &mut impl std::io::Read) -> M8Result<InstrumentWithEq> {
-        let mut buf: Vec<u8> = vec![];
-        reader.read_to_end(&mut buf).unwrap();
-        let mut reader = Reader::new(buf);
+        let mut reader = Reader::from_read(reader)?;
 
         Self::read_from_reader(&mut reader)
     }
 
     pub fn from_reader(reader: &mut Reader, number: u8, version: Version) -> M8Result<Self> {
+        Self::read_kind(reader, number, version)
+            .map_err(|e| e.in_section(Section::Instrument(number as usize)))
+    }
+
+    fn read_kind(reader: &mut Reader, number: u8, version: Version) -> M8Result<Self> {
         let start_pos = reader.pos();
-        let kind = reader.read();
+        let kind = reader.read()?;
 
         let instr = match kind {
             0x00 => Self::WavSynth(WavSynth::from_reader(version, reader, number, version)?),
@@ -392,12 +391,7 @@ impl Instrument {
                 Self::External(ExternalInst::from_reader(version, reader, number)?)
             }
             0xFF => Self::None,
-            _ => {
-                return Err(ParseError(format!(
-                    "Instrument type {} not supported",
-                    kind
-                )))
-            }
+            _ => return Err(reader.invalid_at(start_pos, "instrument kind", kind)),
         };
 
         reader.set_pos(start_pos + Instrument::INSTRUMENT_MEMORY_SIZE);
@@ -36,11 +36,11 @@ impl ADSREnv {
     pub fn from_reader(reader: &mut Reader, dest: u8) -> M8Result<Self> {
         Ok(Self {
             dest,
-            amount: reader.read(),
-            attack: reader.read(),
-            decay: reader.read(),
-            sustain: reader.read(),
-            release: reader.read(),
+            amount: reader.read()?,
+            attack: reader.read()?,
+            decay: reader.read()?,
+            sustain: reader.read()?,
+            release: reader.read()?,
         })
     }
 }
@@ -25,23 +25,23 @@ impl AHDEnv {
 
     pub fn from_reader2(reader: &mut Reader) -> M8Result<Self> {
         let r = Self {
-            dest: reader.read(),
-            amount: reader.read(),
-            attack: reader.read(),
-            hold: reader.read(),
-            decay: reader.read(),
+            dest: reader.read()?,
+            amount: reader.read()?,
+            attack: reader.read()?,
+            hold: reader.read()?,
+            decay: reader.read()?,
         };
-        reader.read();
+        reader.read()?;
         Ok(r)
     }
 
     pub fn from_reader3(reader: &mut Reader, dest: u8) -> M8Result<Self> {
         Ok(Self {
             dest,
-            amount: reader.read(),
-            attack: reader.read(),
-            hold: reader.read(),
-            decay: reader.read(),
+            amount: reader.read()?,
+            attack: reader.read()?,
+            hold: reader.read()?,
+            decay: reader.read()?,
         })
     }
 
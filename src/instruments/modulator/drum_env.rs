@@ -33,10 +33,10 @@ impl DrumEnv {
     pub fn from_reader(reader: &mut Reader, dest: u8) -> M8Result<Self> {
         Ok(Self {
             dest,
-            amount: reader.read(),
-            peak: reader.read(),
-            body: reader.read(),
-            decay: reader.read(),
+            amount: reader.read()?,
+            peak: reader.read()?,
+            body: reader.read()?,
+            decay: reader.read()?,
         })
     }
 }
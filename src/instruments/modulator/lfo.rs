@@ -2,7 +2,7 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::{writer::Writer, Version};
 
-use super::{M8Result, Mod, ParseError, Reader};
+use super::{M8Result, Mod, Reader};
 
 #[repr(u8)]
 #[allow(non_camel_case_types)]
@@ -65,20 +65,16 @@ impl LFO {
     }
 
     pub fn from_reader2(reader: &mut Reader) -> M8Result<Self> {
-        let shape = reader.read();
-        let dest = reader.read();
-        let trigger = reader.read();
+        let shape = reader.read_value("LFO shape")?;
+        let dest = reader.read()?;
+        let trigger_mode = reader.read_value("LFO trigger mode")?;
         let r = Self {
-            shape: shape
-                .try_into()
-                .map_err(|_| ParseError(format!("Invalid LFO shape {}", shape)))?,
+            shape,
             dest,
-            trigger_mode: trigger
-                .try_into()
-                .map_err(|_| ParseError(format!("Invalid lfo trigger mode {}", trigger)))?,
-            freq: reader.read(),
-            amount: reader.read(),
-            retrigger: reader.read(),
+            trigger_mode,
+            freq: reader.read()?,
+            amount: reader.read()?,
+            retrigger: reader.read()?,
         };
 
         Ok(r)
@@ -93,21 +89,17 @@ impl LFO {
     }
 
     pub fn from_reader3(reader: &mut Reader, dest: u8) -> M8Result<Self> {
-        let amount = reader.read();
-        let shape = reader.read();
-        let trigger_mode = reader.read();
-        let freq = reader.read();
-        let retrigger = reader.read();
+        let amount = reader.read()?;
+        let shape = reader.read_value("LFO shape")?;
+        let trigger_mode = reader.read_value("LFO trigger mode")?;
+        let freq = reader.read()?;
+        let retrigger = reader.read()?;
 
         Ok(Self {
             dest,
             amount,
-            shape: shape
-                .try_into()
-                .map_err(|_| ParseError(format!("Invalid LFO shape {}", shape)))?,
-            trigger_mode: trigger_mode
-                .try_into()
-                .map_err(|_| ParseError(format!("Invalid lfo trigger mode {}", trigger_mode)))?,
+            shape,
+            trigger_mode,
             freq,
             retrigger,
         })
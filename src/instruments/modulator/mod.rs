@@ -16,6 +16,12 @@ pub use lfo::*;
 pub use tracking_env::*;
 pub use trig_env::*;
 
+/// Attach the modulator index to a parsing error, the
+/// instrument is set when leaving the instrument parsing.
+pub(crate) fn in_modulator<T>(modulator: usize, r: M8Result<T>) -> M8Result<T> {
+    r.map_err(|e| e.in_section(Section::Modulator { instrument: 0, modulator }))
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum Mod {
     AHDEnv(AHDEnv),
@@ -46,7 +52,7 @@ impl Mod {
 
     pub fn from_reader(reader: &mut Reader) -> M8Result<Self> {
         let start_pos = reader.pos();
-        let first_byte = reader.read();
+        let first_byte = reader.read()?;
         let ty = first_byte >> 4;
         let dest = first_byte & 0x0F;
 
@@ -58,13 +64,22 @@ impl Mod {
             3 => Mod::LFO(LFO::from_reader3(reader, dest)?),
             4 => Mod::TrigEnv(TrigEnv::from_reader(reader, dest)?),
             5 => Mod::TrackingEnv(TrackingEnv::from_reader(reader, dest)?),
-            x => return Err(ParseError(format!("Unknown mod type {}", x))),
+            x => return Err(reader.invalid_at(start_pos, "modulator type", x)),
         };
 
         reader.set_pos(start_pos + Self::SIZE);
         Ok(r)
     }
 
+    /// Read the 4 modulators of an instrument
+    pub(crate) fn read_mods(reader: &mut Reader) -> M8Result<[Mod; 4]> {
+        let mut mods = vec![];
+        for i in 0..4 {
+            mods.push(in_modulator(i, Mod::from_reader(reader))?);
+        }
+        Ok(mods.try_into().unwrap())
+    }
+
     pub fn write(&self, w: &mut Writer) {
         let start = w.pos();
 
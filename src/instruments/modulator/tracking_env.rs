@@ -33,10 +33,10 @@ impl TrackingEnv {
     pub fn from_reader(reader: &mut Reader, dest: u8) -> M8Result<Self> {
         Ok(Self {
             dest,
-            amount: reader.read(),
-            src: reader.read(),
-            lval: reader.read(),
-            hval: reader.read(),
+            amount: reader.read()?,
+            src: reader.read()?,
+            lval: reader.read()?,
+            hval: reader.read()?,
         })
     }
 }
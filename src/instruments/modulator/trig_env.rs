@@ -35,11 +35,11 @@ impl TrigEnv {
     pub fn from_reader(reader: &mut Reader, dest: u8) -> M8Result<Self> {
         Ok(Self {
             dest,
-            amount: reader.read(),
-            attack: reader.read(),
-            hold: reader.read(),
-            decay: reader.read(),
-            src: reader.read(),
+            amount: reader.read()?,
+            attack: reader.read()?,
+            hold: reader.read()?,
+            decay: reader.read()?,
+            src: reader.read()?,
         })
     }
 }
@@ -149,20 +149,20 @@ impl Sampler {
         number: u8,
         version: Version,
     ) -> M8Result<Self> {
-        let name = reader.read_string(12);
+        let name = reader.read_string(12)?;
 
-        let transp_eq = TranspEq::from_version(ver, reader.read());
-        let table_tick = reader.read();
-        let volume = reader.read();
-        let pitch = reader.read();
-        let fine_tune = reader.read();
+        let transp_eq = TranspEq::from_version(ver, reader.read()?);
+        let table_tick = reader.read()?;
+        let volume = reader.read()?;
+        let pitch = reader.read()?;
+        let fine_tune = reader.read()?;
 
-        let play_mode = reader.read();
-        let slice = reader.read();
-        let start = reader.read();
-        let loop_start = reader.read();
-        let length = reader.read();
-        let degrade = reader.read();
+        let play_mode = reader.read_value("sample play mode")?;
+        let slice = reader.read()?;
+        let start = reader.read()?;
+        let loop_start = reader.read()?;
+        let length = reader.read()?;
+        let degrade = reader.read()?;
 
         let synth_params = if version.after(&FIRMWARE_3_0_SONG_VERSION) {
             SynthParams::from_reader3(
@@ -179,7 +179,7 @@ impl Sampler {
         };
 
         reader.set_pos(start_pos + 0x57);
-        let sample_path = reader.read_string(128);
+        let sample_path = reader.read_string(128)?;
 
         Ok(Sampler {
             number,
@@ -189,9 +189,7 @@ impl Sampler {
             synth_params,
 
             sample_path,
-            play_mode: play_mode
-                .try_into()
-                .map_err(|_| ParseError(format!("Invalid play mode")))?,
+            play_mode,
             slice,
             start,
             loop_start,
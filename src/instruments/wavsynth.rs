@@ -217,18 +217,18 @@ impl WavSynth {
         number: u8,
         version: Version,
     ) -> M8Result<Self> {
-        let name = reader.read_string(12);
-        let transp_eq = TranspEq::from_version(ver, reader.read());
-        let table_tick = reader.read();
-        let volume = reader.read();
-        let pitch = reader.read();
-        let fine_tune = reader.read();
+        let name = reader.read_string(12)?;
+        let transp_eq = TranspEq::from_version(ver, reader.read()?);
+        let table_tick = reader.read()?;
+        let volume = reader.read()?;
+        let pitch = reader.read()?;
+        let fine_tune = reader.read()?;
 
-        let shape = reader.read();
-        let size = reader.read();
-        let mult = reader.read();
-        let warp = reader.read();
-        let scan = reader.read();
+        let shape = reader.read_value("wavsynth shape")?;
+        let size = reader.read()?;
+        let mult = reader.read()?;
+        let warp = reader.read()?;
+        let scan = reader.read()?;
         let synth_params = if version.after(&FIRMWARE_3_0_SONG_VERSION) {
             SynthParams::from_reader3(
                 ver,
@@ -250,9 +250,7 @@ impl WavSynth {
             table_tick,
             synth_params,
 
-            shape: shape
-                .try_into()
-                .map_err(|_| ParseError(format!("Invalid wavsynth shape")))?,
+            shape,
             size,
             mult,
             warp,
@@ -16,7 +16,9 @@
 //! ```
 //!
 //! For song writing and file manipulation, you will need to load
-//! the whole file in memory, in order to be able to overwrite it
+//! the whole file in memory, in order to be able to overwrite it.
+//! Bytes not understood by the library are kept as is, so writing
+//! back an unmodified song gives the exact same file.
 //!
 //! ```
 //! use m8_file_parser::*;
@@ -42,6 +44,36 @@
 //! let output_song_data = output_writer.finish();
 //! ```
 //!
+//! A song can also be created from scratch, without any existing file
+//!
+//! ```
+//! use m8_file_parser::*;
+//!
+//! let mut song = Song::new(Version::new(6, 6));
+//! song.name = "NEWSONG".to_string();
+//!
+//! let mut output_writer = writer::Writer::new_song_writer(song.version);
+//! song.write(&mut output_writer).unwrap();
+//! let output_song_data = output_writer.finish();
+//! ```
+//!
+//! Themes can be built and written as theme files
+//!
+//! ```
+//! use m8_file_parser::*;
+//!
+//! let dark = RGB::new(0x10, 0x10, 0x10);
+//! let light = RGB::new(0xE0, 0xE0, 0xE0);
+//! let mut colors = [light; Theme::COLOR_COUNT];
+//! colors[0] = dark;
+//! let theme = Theme::new(colors);
+//!
+//! let mut output_writer = writer::Writer::new_theme_writer();
+//! theme.write(Version::new(6, 6), &mut output_writer);
+//! let theme_data = output_writer.finish();
+//! assert_eq!(Theme::read(&mut theme_data.as_slice()).unwrap(), theme);
+//! ```
+//!
 //! You also can perform more complex copies of chain, that
 //! will copy intrument/eq/table definitions required to copy
 //! a chain from a song to another
@@ -65,24 +97,42 @@
 //! let final_chain = mapping.out_chain(chain);
 //! to_song.song.steps[2] = final_chain;
 //! ```
+mod cleanup;
+mod diff;
 mod eq;
 mod fx;
 mod instruments;
+mod merge;
+mod migration;
 pub mod reader;
 pub mod remapper;
+mod scala;
 mod scale;
 mod settings;
+mod smf;
 mod songs;
+#[cfg(test)]
+mod test_utils;
 mod theme;
+mod timeline;
+mod validation;
 mod version;
 pub mod param_gatherer;
 pub mod writer;
 
+pub use cleanup::*;
+pub use diff::*;
 pub use eq::*;
 pub use fx::*;
 pub use instruments::*;
+pub use merge::*;
+pub use migration::*;
+pub use scala::*;
 pub use scale::*;
 pub use settings::*;
+pub use smf::*;
 pub use songs::*;
 pub use theme::*;
+pub use timeline::*;
+pub use validation::*;
 pub use version::*;
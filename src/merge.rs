@@ -0,0 +1,297 @@
+//! Three-way merge of songs edited from a common base.
+use crate::cleanup::identity_remapper;
+use crate::remapper::{try_allocate, MoveKind, RemapperDescriptorBuilder};
+use crate::songs::*;
+
+/// How [`Song::merge`] handles elements edited differently on both sides
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum MergeStrategy {
+    /// Keep our version and report a conflict
+    #[default]
+    ReportConflicts,
+
+    /// Keep our version in place and copy their version of the
+    /// conflicting phrases and chains to free slots, not referenced by
+    /// the song, where it can be picked up on the device. Relocated
+    /// chains use the relocated phrases. Song cells, instruments,
+    /// tables and EQs are still reported as conflicts.
+    RelocateTheirs,
+}
+
+/// Element edited differently on both sides, our version is kept
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum MergeConflict {
+    SongCell { row: usize, track: usize },
+    ChainStep { chain: usize, step: usize },
+    PhraseStep { phrase: usize, step: usize },
+    Instrument { instrument: usize },
+    Table { table: usize },
+    Eq { eq: usize },
+}
+
+/// Their version of a conflicting phrase or chain, copied to a free slot
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Relocation {
+    /// Either a phrase or a chain
+    pub kind: MoveKind,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl RemapperDescriptorBuilder for Vec<Relocation> {
+    fn moved(&mut self, kind: MoveKind, from: usize, to: usize) {
+        self.push(Relocation { kind, from, to })
+    }
+}
+
+/// Outcome of [`Song::merge`]
+pub struct MergeResult {
+    pub song: Song,
+    pub conflicts: Vec<MergeConflict>,
+    pub relocations: Vec<Relocation>,
+}
+
+/// Three-way merge of a single element, None if both sides changed
+/// it differently.
+fn merge_element<T: PartialEq + Clone>(base: &T, ours: &T, theirs: &T) -> Option<T> {
+    if ours == theirs || theirs == base {
+        Some(ours.clone())
+    } else if ours == base {
+        Some(theirs.clone())
+    } else {
+        None
+    }
+}
+
+/// Merge every element of a list, returning the conflicting indices
+fn merge_elements<T: PartialEq + Clone>(base: &[T], ours: &[T], theirs: &[T], merged: &mut [T]) -> Vec<usize> {
+    let mut conflicts = vec![];
+    for (i, out) in merged.iter_mut().enumerate() {
+        match merge_element(&base[i], &ours[i], &theirs[i]) {
+            Some(element) => *out = element,
+            None => conflicts.push(i),
+        }
+    }
+    conflicts
+}
+
+/// Allocation state of the slots, relocated elements only go
+/// in the slots free in every song.
+fn allocated_slots(songs: [&Song; 4], count: usize, is_free: impl Fn(&Song, u8) -> bool) -> Vec<bool> {
+    (0..count).map(|i| !songs.iter().all(|song| is_free(song, i as u8))).collect()
+}
+
+impl Song {
+    /// Merge the changes made in `ours` and `theirs` since `base`, at
+    /// the song cell, chain step, phrase step, instrument, table and
+    /// EQ level. Edits which don't overlap are all kept, the other
+    /// settings come from `ours`. All the songs must have the same
+    /// version.
+    pub fn merge(
+        base: &Song,
+        ours: &Song,
+        theirs: &Song,
+        strategy: MergeStrategy,
+    ) -> Result<MergeResult, String> {
+        if base.version != ours.version || base.version != theirs.version {
+            return Err(format!(
+                "Cannot merge songs of different versions: base {}, ours {}, theirs {}",
+                base.version, ours.version, theirs.version
+            ));
+        }
+
+        let mut song = ours.clone();
+        let mut conflicts = vec![];
+
+        for i in merge_elements(&base.song.steps, &ours.song.steps, &theirs.song.steps, &mut song.song.steps) {
+            let (row, track) = (i / SongSteps::TRACK_COUNT, i % SongSteps::TRACK_COUNT);
+            conflicts.push(MergeConflict::SongCell { row, track });
+        }
+
+        for instrument in merge_elements(&base.instruments, &ours.instruments, &theirs.instruments, &mut song.instruments) {
+            conflicts.push(MergeConflict::Instrument { instrument });
+        }
+
+        for table in merge_elements(&base.tables, &ours.tables, &theirs.tables, &mut song.tables) {
+            conflicts.push(MergeConflict::Table { table });
+        }
+
+        for eq in merge_elements(&base.eqs, &ours.eqs, &theirs.eqs, &mut song.eqs) {
+            conflicts.push(MergeConflict::Eq { eq });
+        }
+
+        let relocate = strategy == MergeStrategy::RelocateTheirs;
+        let mut remapper = identity_remapper(song.version);
+
+        let mut phrase_slots = allocated_slots([base, ours, theirs, &song], Song::N_PHRASES, |s, i| {
+            let referenced = s.chains.iter().any(|c| c.steps.iter().any(|step| step.phrase == i));
+            s.phrases[i as usize].is_empty() && !referenced
+        });
+        for phrase in 0..Song::N_PHRASES {
+            let steps = merge_elements(
+                &base.phrases[phrase].steps,
+                &ours.phrases[phrase].steps,
+                &theirs.phrases[phrase].steps,
+                &mut song.phrases[phrase].steps,
+            );
+
+            let slot = if steps.is_empty() || !relocate {
+                None
+            } else {
+                try_allocate(&phrase_slots, phrase as u8)
+            };
+
+            match slot {
+                Some(to) => {
+                    phrase_slots[to] = true;
+                    remapper.phrase_mapping.mapping[phrase] = to as u8;
+                    remapper.phrase_mapping.to_move.push(phrase as u8);
+                }
+                None => {
+                    conflicts.extend(steps.into_iter().map(|step| MergeConflict::PhraseStep { phrase, step }))
+                }
+            }
+        }
+
+        let mut chain_slots = allocated_slots([base, ours, theirs, &song], Song::N_CHAINS, |s, i| {
+            s.chains[i as usize].is_empty() && !s.song.steps.contains(&i)
+        });
+        for chain in 0..Song::N_CHAINS {
+            let steps = merge_elements(
+                &base.chains[chain].steps,
+                &ours.chains[chain].steps,
+                &theirs.chains[chain].steps,
+                &mut song.chains[chain].steps,
+            );
+
+            let slot = if steps.is_empty() || !relocate {
+                None
+            } else {
+                try_allocate(&chain_slots, chain as u8)
+            };
+
+            match slot {
+                Some(to) => {
+                    chain_slots[to] = true;
+                    remapper.chain_mapping.mapping[chain] = to as u8;
+                    remapper.chain_mapping.to_move.push(chain as u8);
+                }
+                None => {
+                    conflicts.extend(steps.into_iter().map(|step| MergeConflict::ChainStep { chain, step }))
+                }
+            }
+        }
+
+        // relocated chains use the relocated phrases
+        remapper.apply(theirs, &mut song);
+
+        let mut relocations = vec![];
+        remapper.describe(&mut relocations);
+
+        Ok(MergeResult { song, conflicts, relocations })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::merge::*;
+    use crate::test_utils::read_song;
+    use crate::Note;
+
+    fn base_song() -> Song {
+        let mut base = read_song("./examples/songs/V6EMPTY.m8s");
+        base.song.steps[0] = 0;
+        base.chains[0].steps[0].phrase = 1;
+        base.phrases[1].steps[0].note = Note(0x24);
+        base
+    }
+
+    #[test]
+    fn test_merge_disjoint_edits() {
+        let base = base_song();
+        let trackeq = read_song("./examples/songs/TRACKEQ.m8s");
+
+        let mut ours = base.clone();
+        ours.song.steps[1] = 0;
+        ours.phrases[1].steps[1].note = Note(0x26);
+        ours.eqs[2] = trackeq.eqs[1].clone();
+
+        let mut theirs = base.clone();
+        theirs.song.steps[8] = 0;
+        theirs.chains[0].steps[1].phrase = 1;
+        theirs.phrases[1].steps[2].note = Note(0x28);
+        theirs.instruments[4] = trackeq.instruments[1].clone();
+        theirs.tables[4].steps[0].transpose = 3;
+
+        let merged = Song::merge(&base, &ours, &theirs, MergeStrategy::ReportConflicts).unwrap();
+        assert_eq!(merged.conflicts, vec![]);
+        assert_eq!(merged.relocations, vec![]);
+
+        let mut expected = ours.clone();
+        expected.song.steps[8] = 0;
+        expected.chains[0].steps[1].phrase = 1;
+        expected.phrases[1].steps[2].note = Note(0x28);
+        expected.instruments[4] = trackeq.instruments[1].clone();
+        expected.tables[4].steps[0].transpose = 3;
+        assert!(merged.song == expected);
+
+        let swapped = Song::merge(&base, &theirs, &ours, MergeStrategy::ReportConflicts).unwrap();
+        assert!(swapped.song == expected);
+    }
+
+    #[test]
+    fn test_merge_conflicts() {
+        let base = base_song();
+        let (mut ours, mut theirs) = (base.clone(), base.clone());
+        for (song, value) in [(&mut ours, 1), (&mut theirs, 2)] {
+            song.song.steps[0] = value;
+            song.chains[0].steps[1].phrase = 1;
+            song.chains[0].steps[1].transpose = value;
+            song.phrases[1].steps[0].note = Note(0x30 + value);
+            song.tables[0x90].steps[0].velocity = value;
+        }
+        theirs.phrases[1].steps[5].note = Note(0x40);
+
+        let merged = Song::merge(&base, &ours, &theirs, MergeStrategy::ReportConflicts).unwrap();
+        assert_eq!(
+            merged.conflicts,
+            vec![
+                MergeConflict::SongCell { row: 0, track: 0 },
+                MergeConflict::Table { table: 0x90 },
+                MergeConflict::PhraseStep { phrase: 1, step: 0 },
+                MergeConflict::ChainStep { chain: 0, step: 1 },
+            ]
+        );
+        assert_eq!(merged.song.song.steps[0], 1);
+        assert_eq!(merged.song.phrases[1].steps[0].note, Note(0x31));
+        assert_eq!(merged.song.phrases[1].steps[5].note, Note(0x40));
+
+        let relocated = Song::merge(&base, &ours, &theirs, MergeStrategy::RelocateTheirs).unwrap();
+        assert_eq!(
+            relocated.conflicts,
+            vec![MergeConflict::SongCell { row: 0, track: 0 }, MergeConflict::Table { table: 0x90 }]
+        );
+        assert_eq!(
+            relocated.relocations,
+            vec![
+                Relocation { kind: MoveKind::PHR, from: 1, to: 2 },
+                Relocation { kind: MoveKind::CHN, from: 0, to: 3 },
+            ]
+        );
+
+        let song = &relocated.song;
+        assert!(song.chains[0] == merged.song.chains[0]);
+        assert!(song.phrases[1] == merged.song.phrases[1]);
+        assert!(song.phrases[2] == theirs.phrases[1]);
+        assert_eq!(song.chains[3].steps[0].phrase, 2);
+        assert_eq!(song.chains[3].steps[1].phrase, 2);
+        assert_eq!(song.chains[3].steps[1].transpose, 2);
+    }
+
+    #[test]
+    fn test_merge_versions() {
+        let base = read_song("./examples/songs/V6EMPTY.m8s");
+        let other = read_song("./examples/songs/V6_6EMPTY.m8s");
+        assert!(Song::merge(&base, &base, &other, MergeStrategy::default()).is_err());
+    }
+}
@@ -0,0 +1,547 @@
+//! Conversion of songs between firmware file layouts.
+use crate::eq::Equ;
+use crate::fx::*;
+use crate::instruments::*;
+use crate::reader::Reader;
+use crate::remapper::EQ_TRACKING_COMMAND_NAMES;
+use crate::settings::*;
+use crate::songs::*;
+use crate::version::*;
+
+/// Change losing information when converting a song to
+/// the layout of an older firmware.
+#[derive(PartialEq, Debug, Clone)]
+pub enum DowngradeLoss {
+    /// The instrument EQ does not exist in the older layout, the
+    /// instrument has no EQ anymore.
+    InstrumentEq { instrument: usize, eq: u8 },
+
+    /// Content of an EQ past the EQ count of the older layout
+    Eq { eq: usize },
+
+    /// Hypersynth oscillator shape, Saw before 6.6
+    HyperSynthShape { instrument: usize, shape: HyperSynthShape },
+
+    /// Effect without equivalent in the older firmware, or referencing
+    /// a removed EQ, removed from the phrase step.
+    PhraseFx { phrase: usize, step: usize, command: &'static str },
+
+    /// Same as `PhraseFx` for a table step
+    TableFx { table: usize, step: usize, command: &'static str },
+
+    /// Limiter attack, release and soft clip
+    LimiterAttackRelease((u8, u8, bool)),
+    OttLevel(u8),
+    OttConfiguration(OttConfiguration),
+    MfxKind(FxKind),
+    ReverbShimmer(u8),
+
+    /// Groove played with another PPQN than 24
+    GroovePpqn { groove: usize, ppqn: u8 },
+
+    /// At least one row was bookmarked
+    RowBookmarks,
+}
+
+/// Give an optional field its value for a target layout: the current one
+/// (or the default of the target firmware) if the layout has the field,
+/// None otherwise. Returns the value removed if it was not the default
+/// of the source firmware.
+fn convert_field<T: PartialEq>(
+    field: &mut Option<T>,
+    source_default: Option<T>,
+    target_default: Option<T>,
+) -> Option<T> {
+    match target_default {
+        Some(dflt) => {
+            if field.is_none() {
+                *field = Some(dflt);
+            }
+            None
+        }
+        None => field.take().filter(|lost| Some(lost) != source_default.as_ref()),
+    }
+}
+
+/// Effect conversion for the target layout, returning the source command
+/// name of effects to be removed.
+struct FxConversion {
+    translation: FxTranslation,
+    eq_commands: Vec<u8>,
+    eq_count: usize,
+}
+
+impl FxConversion {
+    fn convert(&self, fx: FX, instrument: Option<&Instrument>) -> Result<FX, &'static str> {
+        let name = || self.translation.instr_command_name(fx, instrument).unwrap_or("?");
+
+        if self.eq_commands.contains(&fx.command) && fx.value as usize >= self.eq_count {
+            return Err(name());
+        }
+
+        self.translation.translate_instr(fx, instrument).ok_or_else(name)
+    }
+
+    /// Convert the effects of steps in place, calling `lost` with the
+    /// step number and command of every removed effect. Every step
+    /// comes with the instrument playing it, if known.
+    fn convert_steps<'a, 'i, IT, F>(&self, steps: IT, mut lost: F)
+    where
+        IT: Iterator<Item = ([&'a mut FX; 3], Option<&'i Instrument>)>,
+        F: FnMut(usize, &'static str),
+    {
+        for (step, (fxs, instrument)) in steps.enumerate() {
+            for fx in fxs {
+                match self.convert(*fx, instrument) {
+                    Ok(converted) => *fx = converted,
+                    Err(command) => {
+                        lost(step, command);
+                        *fx = FX::default();
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Instrument {
+    /// Move the EQ reference of the instrument from a layout
+    /// to another one, EQ numbers past the EQ count of a layout
+    /// mean "no EQ". Returns the EQ if it has been removed.
+    fn migrate_eq(&mut self, from: &Offsets, to: &Offsets, version: Version) -> Option<u8> {
+        if let Instrument::MIDIOut(mo) = self {
+            // MIDI out only carry an EQ in the 4.0 packing
+            mo.mods.associated_eq = if version.after(&FIRMWARE_5_0_SONG_VERSION) {
+                0xFF
+            } else {
+                to.instrument_eq_count as u8
+            };
+            return None;
+        }
+
+        let eq = self.equ()?;
+        if eq as usize >= from.instrument_eq_count.min(to.instrument_eq_count) {
+            self.set_eq(to.instrument_eq_count as u8);
+        }
+
+        if (to.instrument_eq_count..from.instrument_eq_count).contains(&(eq as usize)) {
+            Some(eq)
+        } else {
+            None
+        }
+    }
+}
+
+impl HyperSynth {
+    /// Give the shape its value for a version, returning the shape
+    /// if it has been removed.
+    fn migrate_shape(&mut self, source: Version, version: Version) -> Option<HyperSynthShape> {
+        let default_shape = |ver: Version| ver.at_least(6, 6).then(HyperSynthShape::default);
+        let lost = convert_field(&mut self.shape, default_shape(source), default_shape(version));
+        if let Some(shape) = self.shape {
+            self.synth_params.shape = shape.into();
+        }
+        lost
+    }
+}
+
+impl Song {
+    /// Upgrade the song to the file layout of a more recent firmware.
+    ///
+    /// EQ references of instruments are moved to the new EQ count,
+    /// and fields introduced in between get the value they have in a
+    /// new song of the target firmware. Effects and global EQs are not
+    /// parsed, write the migrated song with a writer from
+    /// [`Writer::new_converted_song_writer`] to carry them over.
+    ///
+    /// [`Writer::new_converted_song_writer`]: crate::writer::Writer::new_converted_song_writer
+    pub fn migrate_to(&mut self, version: Version) -> Result<(), String> {
+        self.check_convertible(version)?;
+
+        if !version.after(&self.version) {
+            return Err(format!(
+                "Cannot migrate song from {} to older version {}",
+                self.version, version
+            ));
+        }
+
+        self.convert_layout(version);
+        Ok(())
+    }
+
+    /// Convert the song to the file layout of an older firmware
+    /// (4.0 or above).
+    ///
+    /// Features missing from the older firmware are removed, or set
+    /// back to their default value. The returned report lists every
+    /// parsed value lost in the process, when it differs from the
+    /// default of the source firmware:
+    ///
+    ///  * instrument EQs and EQ content past the EQ count of the target
+    ///    layout (32 EQs before 4.1),
+    ///  * phrase and table effects missing from the command table of the
+    ///    target firmware, or referencing a removed EQ,
+    ///  * the hypersynth shape, the only instrument parameter depending
+    ///    on the firmware: every instrument kind exists from 4.0, so
+    ///    instruments themselves are kept,
+    ///  * limiter attack/release, OTT level and configuration, MFX kind
+    ///    and reverb shimmer of the mixer and effect settings,
+    ///  * groove PPQN and row bookmarks.
+    ///
+    /// Bytes not parsed by the library are not covered. As with
+    /// [`Song::migrate_to`], write the result with a writer from
+    /// [`Writer::new_converted_song_writer`].
+    ///
+    /// [`Writer::new_converted_song_writer`]: crate::writer::Writer::new_converted_song_writer
+    pub fn downgrade_to(&mut self, version: Version) -> Result<Vec<DowngradeLoss>, String> {
+        self.check_convertible(version)?;
+
+        if !self.version.after(&version) {
+            return Err(format!(
+                "Cannot downgrade song from {} to newer version {}",
+                self.version, version
+            ));
+        }
+
+        Ok(self.convert_layout(version))
+    }
+
+    /// Content of the file of a song converted to another version
+    /// before writing it: an empty song file of the target version,
+    /// with the effect and global EQs and the trailing bytes past the
+    /// known layout of the original file moved to their new place.
+    pub(crate) fn converted_file(original: &[u8], version: Version) -> Result<Vec<u8>, String> {
+        let mut reader = Reader::new(original.to_vec());
+        let source = Version::from_reader(&mut reader).map_err(|e| e.to_string())?;
+        Self::check_versions(source, version)?;
+
+        let from = Self::offsets_for(source);
+        let to = Self::offsets_for(version);
+        let source_size = from.file_size(source);
+        if original.len() < source_size {
+            return Err(format!(
+                "Song file is truncated, {} bytes instead of {}",
+                original.len(),
+                source_size
+            ));
+        }
+
+        let mut file = Self::blank_file(version);
+        let non_instrument_eqs = (from.eq_count() - from.instrument_eq_count) * Equ::V4_SIZE;
+        let source_eqs = from.eq + from.instrument_eq_count * Equ::V4_SIZE;
+        let target_eqs = to.eq + to.instrument_eq_count * Equ::V4_SIZE;
+        file[target_eqs..target_eqs + non_instrument_eqs]
+            .copy_from_slice(&original[source_eqs..source_eqs + non_instrument_eqs]);
+
+        file.extend_from_slice(&original[source_size..]);
+        Ok(file)
+    }
+
+    fn check_convertible(&self, version: Version) -> Result<(), String> {
+        Self::check_versions(self.version, version)
+    }
+
+    fn check_versions(source: Version, version: Version) -> Result<(), String> {
+        for v in [source, version] {
+            if !v.after(&FIRMWARE_4_0_SONG_VERSION) {
+                return Err(format!(
+                    "Only version 4.0 or above song can be converted, got {}",
+                    v
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn convert_layout(&mut self, version: Version) -> Vec<DowngradeLoss> {
+        let mut losses = vec![];
+        let from = self.offsets();
+        let to = Self::offsets_for(version);
+
+        for (instrument, instr) in self.instruments.iter_mut().enumerate() {
+            if let Some(eq) = instr.migrate_eq(from, to, version) {
+                losses.push(DowngradeLoss::InstrumentEq { instrument, eq });
+            }
+
+            if let Instrument::HyperSynth(hs) = instr {
+                let lost = hs.migrate_shape(self.version, version);
+                losses.extend(lost.map(|shape| DowngradeLoss::HyperSynthShape { instrument, shape }));
+            }
+        }
+
+        for (eq, equ) in self.eqs.iter().enumerate().skip(to.instrument_eq_count) {
+            if !equ.is_empty() {
+                losses.push(DowngradeLoss::Eq { eq });
+            }
+        }
+        self.eqs.resize(to.instrument_eq_count, Equ::empty());
+
+        let source_mixer = MixerSettings::default_ver(self.version);
+        let mixer = MixerSettings::default_ver(version);
+        let lost = convert_field(
+            &mut self.mixer_settings.limiter.attack_release,
+            source_mixer.limiter.attack_release,
+            mixer.limiter.attack_release,
+        );
+        losses.extend(lost.map(DowngradeLoss::LimiterAttackRelease));
+
+        let lost = convert_field(
+            &mut self.mixer_settings.ott_level,
+            source_mixer.ott_level,
+            mixer.ott_level,
+        );
+        losses.extend(lost.map(DowngradeLoss::OttLevel));
+
+        let source_effects = EffectsSettings::default_ver(self.version);
+        let effects = EffectsSettings::default_ver(version);
+        let current = &mut self.effects_settings;
+        let lost = convert_field(
+            &mut current.ott_configuration,
+            source_effects.ott_configuration,
+            effects.ott_configuration,
+        );
+        losses.extend(lost.map(DowngradeLoss::OttConfiguration));
+
+        let lost = convert_field(&mut current.mfx_kind, source_effects.mfx_kind, effects.mfx_kind);
+        losses.extend(lost.map(DowngradeLoss::MfxKind));
+
+        let lost = convert_field(
+            &mut current.reverb_shimmer,
+            source_effects.reverb_shimmer,
+            effects.reverb_shimmer,
+        );
+        losses.extend(lost.map(DowngradeLoss::ReverbShimmer));
+
+        for (groove, grv) in self.grooves.iter_mut().enumerate() {
+            let lost = convert_field(
+                &mut grv.ppqn,
+                Groove::default_ver(grv.number, self.version).ppqn,
+                Groove::default_ver(grv.number, version).ppqn,
+            );
+            losses.extend(lost.map(|ppqn| DowngradeLoss::GroovePpqn { groove, ppqn }));
+        }
+
+        let lost = convert_field(
+            &mut self.song.row_bookmarks,
+            SongSteps::default_ver(self.version).row_bookmarks,
+            SongSteps::default_ver(version).row_bookmarks,
+        );
+        losses.extend(lost.map(|_| DowngradeLoss::RowBookmarks));
+
+        let conversion = FxConversion {
+            translation: FxTranslation::new(self.version, version),
+            eq_commands: FX::fx_command_names(self.version).find_indices(&EQ_TRACKING_COMMAND_NAMES),
+            eq_count: to.instrument_eq_count,
+        };
+
+        for (phrase, ph) in self.phrases.iter_mut().enumerate() {
+            let instruments = ph.step_instruments(&self.instruments);
+            let steps = ph.steps.iter_mut().map(|s| [&mut s.fx1, &mut s.fx2, &mut s.fx3]).zip(instruments);
+            conversion.convert_steps(steps, |step, command| {
+                losses.push(DowngradeLoss::PhraseFx { phrase, step, command })
+            });
+            ph.version = version;
+        }
+
+        for (table, tbl) in self.tables.iter_mut().enumerate() {
+            let instrument = self.instruments.get(table);
+            let steps = tbl.steps.iter_mut().map(|s| ([&mut s.fx1, &mut s.fx2, &mut s.fx3], instrument));
+            conversion.convert_steps(steps, |step, command| {
+                losses.push(DowngradeLoss::TableFx { table, step, command })
+            });
+            tbl.version = version;
+        }
+
+        self.version = version;
+        losses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::migration::*;
+    use crate::test_utils::{fx, read_song};
+    use crate::writer::Writer;
+
+    fn write_and_read(song: &Song) -> Song {
+        let mut w = Writer::new_song_writer(song.version);
+        song.write(&mut w).expect("Could not write song");
+        let written = w.finish();
+        assert_eq!(written.len(), song.offsets().file_size(song.version));
+
+        let mut reader = Reader::new(written);
+        Song::read_from_reader(&mut reader).expect("Could not parse written song")
+    }
+
+    #[test]
+    fn test_migrate_empty_songs() {
+        for path in ["./examples/songs/V4EMPTY.m8s", "./examples/songs/V5EMPTY.m8s"] {
+            let mut expected = read_song("./examples/songs/V6_6EMPTY.m8s");
+            let mut song = read_song(path);
+            song.migrate_to(expected.version).expect("Could not migrate song");
+            assert_eq!(song.eqs.len(), 0x80);
+
+            // user settings of the original song are kept
+            expected.name = song.name.clone();
+            expected.directory = song.directory.clone();
+            expected.midi_settings = song.midi_settings.clone();
+            assert_eq!(song, expected, "{path}");
+            assert_eq!(write_and_read(&song), song, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_migrate_instrument_eq() {
+        let with_eq = read_song("./examples/songs/TRACKEQ.m8s");
+        let with_midi = read_song("./examples/songs/TEST-FILE.m8s");
+
+        let mut song = read_song("./examples/songs/V4EMPTY.m8s");
+        song.instruments[1] = with_eq.instruments[1].clone();
+        song.instruments[1].set_eq(3);
+        song.instruments[2] = with_eq.instruments[2].clone();
+        song.instruments[2].set_eq(32);
+        song.instruments[6] = with_midi.instruments[6].clone();
+        song.eqs[3] = with_eq.eqs[1].clone();
+
+        song.migrate_to(Version::new(6, 2)).expect("Could not migrate song");
+        assert_eq!(song.instruments[1].equ(), Some(3));
+        assert_eq!(song.instruments[2].equ(), Some(0x80));
+        assert_eq!(song.eqs.len(), 0x80);
+
+        let reread = write_and_read(&song);
+        assert_eq!(reread, song);
+        assert_eq!(reread.eqs[3], song.eqs[3]);
+        assert!(reread.mixer_settings.ott_level.is_some());
+        assert!(reread.effects_settings.mfx_kind.is_some());
+        assert!(reread.grooves[0].ppqn.is_none());
+    }
+
+    #[test]
+    fn test_migrate_hypersynth_shape() {
+        let mut song = read_song("./examples/songs/TRACKEQ.m8s");
+        song.migrate_to(Version::new(6, 6)).expect("Could not migrate song");
+        match &song.instruments[127] {
+            Instrument::HyperSynth(hs) => assert_eq!(hs.shape, Some(HyperSynthShape::Saw)),
+            _ => panic!("Expected a hypersynth"),
+        }
+        assert_eq!(write_and_read(&song), song);
+    }
+
+    #[test]
+    fn test_convert_unparsed_bytes() {
+        let mut song_data = std::fs::read("./examples/songs/V4EMPTY.m8s").expect("Could not open song");
+        let eqs_size = 4 * Equ::V4_SIZE;
+        let global_eqs = |ofs: &Offsets| ofs.eq + ofs.instrument_eq_count * Equ::V4_SIZE;
+        let source_eqs = global_eqs(&V4_OFFSETS);
+        for (i, b) in song_data[source_eqs..source_eqs + eqs_size].iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        song_data.extend_from_slice(b"TRAILING");
+
+        let mut song = Song::read_from_reader(&mut Reader::new(song_data.clone())).expect("Could not parse song");
+        let source_version = song.version;
+        song.migrate_to(Version::new(6, 2)).expect("Could not migrate song");
+
+        let mut w = Writer::new_converted_song_writer(&song_data, song.version).expect("Could not convert file");
+        song.write(&mut w).expect("Could not write song");
+        let migrated = w.finish();
+
+        let target_eqs = global_eqs(song.offsets());
+        assert_eq!(
+            migrated[target_eqs..target_eqs + eqs_size],
+            song_data[source_eqs..source_eqs + eqs_size]
+        );
+        assert_eq!(migrated.len(), target_eqs + eqs_size + 8);
+        assert!(migrated.ends_with(b"TRAILING"));
+        assert_eq!(Song::read_from_reader(&mut Reader::new(migrated.clone())).unwrap(), song);
+
+        song.downgrade_to(source_version).expect("Could not downgrade song");
+        let mut w = Writer::new_converted_song_writer(&migrated, song.version).expect("Could not convert file");
+        song.write(&mut w).expect("Could not write song");
+        let downgraded = w.finish();
+        assert_eq!(downgraded[source_eqs..], song_data[source_eqs..]);
+
+        assert!(Writer::new_converted_song_writer(&song_data[..source_eqs], song.version).is_err());
+    }
+
+    #[test]
+    fn test_downgrade_empty_song() {
+        let mut expected = read_song("./examples/songs/V5EMPTY.m8s");
+        let mut song = read_song("./examples/songs/V6_6EMPTY.m8s");
+        let losses = song.downgrade_to(expected.version).expect("Could not downgrade song");
+        assert!(losses.is_empty(), "{losses:?}");
+
+        expected.name = song.name.clone();
+        expected.directory = song.directory.clone();
+        expected.midi_settings = song.midi_settings.clone();
+        assert_eq!(song, expected);
+        assert_eq!(write_and_read(&song), song);
+    }
+
+    #[test]
+    fn test_downgrade_losses() {
+        let with_eq = read_song("./examples/songs/TRACKEQ.m8s");
+        let mut song = read_song("./examples/songs/V6_6EMPTY.m8s");
+        song.instruments[1] = with_eq.instruments[1].clone();
+        song.instruments[1].set_eq(40);
+        song.instruments[2] = with_eq.instruments[2].clone();
+        song.instruments[2].set_eq(2);
+        song.instruments[3] = with_eq.instruments[127].clone();
+        if let Instrument::HyperSynth(hs) = &mut song.instruments[3] {
+            hs.number = 3;
+            hs.shape = Some(HyperSynthShape::SineFold);
+        }
+        song.eqs[40] = with_eq.eqs[1].clone();
+        song.mixer_settings.limiter.attack_release = Some((1, 2, true));
+        song.mixer_settings.ott_level = Some(5);
+        song.effects_settings.ott_configuration = Some(OttConfiguration { time: 3, color: 4 });
+        song.effects_settings.mfx_kind = Some(FxKind::Phaser);
+        song.effects_settings.reverb_shimmer = Some(6);
+        song.grooves[2].ppqn = Some(1);
+        song.song.row_bookmarks.as_mut().unwrap()[3] = 1;
+        song.phrases[4].steps[2].fx2 = fx(&song, "OTT", 0x10);
+        song.phrases[4].steps[3].fx1 = fx(&song, "XMM", 0x20);
+        song.tables[5].steps[1].fx3 = fx(&song, "EQI", 40);
+
+        let losses = song.downgrade_to(Version::new(4, 0)).expect("Could not downgrade song");
+        assert_eq!(
+            losses,
+            vec![
+                DowngradeLoss::InstrumentEq { instrument: 1, eq: 40 },
+                DowngradeLoss::HyperSynthShape { instrument: 3, shape: HyperSynthShape::SineFold },
+                DowngradeLoss::Eq { eq: 40 },
+                DowngradeLoss::LimiterAttackRelease((1, 2, true)),
+                DowngradeLoss::OttLevel(5),
+                DowngradeLoss::OttConfiguration(OttConfiguration { time: 3, color: 4 }),
+                DowngradeLoss::MfxKind(FxKind::Phaser),
+                DowngradeLoss::ReverbShimmer(6),
+                DowngradeLoss::GroovePpqn { groove: 2, ppqn: 1 },
+                DowngradeLoss::RowBookmarks,
+                DowngradeLoss::PhraseFx { phrase: 4, step: 2, command: "OTT" },
+                DowngradeLoss::TableFx { table: 5, step: 1, command: "EQI" },
+            ]
+        );
+
+        assert_eq!(song.instruments[1].equ(), Some(32));
+        assert_eq!(song.instruments[2].equ(), Some(2));
+        assert!(song.phrases[4].steps[2].fx2.is_empty());
+
+        let commands = FX::fx_command_names(song.version);
+        assert_eq!(commands.try_render(song.phrases[4].steps[3].fx1.command), Some("XCM"));
+        assert_eq!(write_and_read(&song), song);
+    }
+
+    #[test]
+    fn test_migrate_rejects_older() {
+        let mut song = read_song("./examples/songs/V6EMPTY.m8s");
+        assert!(song.migrate_to(Version::new(4, 0)).is_err());
+
+        let mut song = read_song("./examples/songs/TEST-FILE.m8s");
+        assert!(song.migrate_to(Version::new(6, 6)).is_err());
+
+        let mut song = read_song("./examples/songs/V4EMPTY.m8s");
+        assert!(song.downgrade_to(Version::new(6, 0)).is_err());
+        assert!(song.downgrade_to(Version::new(3, 0)).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::*;
+use crate::scale::NOTE_NAMES;
 
 /// Interface to gather and display parameters in a semi
 /// automated manner
@@ -180,6 +181,32 @@ impl Describable for Equ {
     }
 }
 
+impl Describable for Groove {
+    fn describe<PG : ParameterGatherer>(&self, pg: PG, _ver: Version) -> PG {
+        let pg = self.steps.iter()
+            .enumerate()
+            .fold(pg, |ipg, (i, step)| ipg.hex(&format!("STEP {:X}", i), *step));
+
+        match self.ppqn {
+            None => pg,
+            Some(_) => pg.str("PPQN", &self.active_ppqn().to_string())
+        }
+    }
+}
+
+impl Describable for Scale {
+    fn describe<PG : ParameterGatherer>(&self, pg: PG, _ver: Version) -> PG {
+        let pg = pg.str(params::NAME, &self.name);
+        self.notes.iter()
+            .zip(NOTE_NAMES.iter())
+            .fold(pg, |ipg, (offset, note)| {
+                ipg.nest_f(note, |nipg|
+                    nipg.bool("EN", offset.enabled)
+                        .float("OFFSET", offset.semitones as f64))
+            })
+    }
+}
+
 impl Describable for Operator {
     fn describe<PG : ParameterGatherer>(&self, pg: PG, _ver: Version) -> PG{
         return pg
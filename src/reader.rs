@@ -1,19 +1,229 @@
 use std::fmt;
 
-#[derive(PartialEq, Debug)]
-pub struct ParseError(pub String);
+/// Part of a file in which a parsing error was found
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Section {
+    /// Error not attributed to a specific part, like a file too
+    /// short to be read at all.
+    File,
+    /// Song header: version, name, tempo, MIDI and mixer settings
+    Header,
+    Groove(usize),
+    SongSteps,
+    Phrase(usize),
+    Chain(usize),
+    Table(usize),
+    Instrument(usize),
+    Modulator { instrument: usize, modulator: usize },
+    EffectsSettings,
+    MidiMapping(usize),
+    Scale(usize),
+    Eq(usize),
+    Theme,
+    /// Scala `.scl` scale or `.kbm` keyboard mapping
+    Scala,
+    /// Chunk of a standard MIDI file, the header being the first one
+    MidiChunk(usize),
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Section::File => write!(f, "file"),
+            Section::Header => write!(f, "song header"),
+            Section::Groove(i) => write!(f, "groove {:02X}", i),
+            Section::SongSteps => write!(f, "song steps"),
+            Section::Phrase(i) => write!(f, "phrase {:02X}", i),
+            Section::Chain(i) => write!(f, "chain {:02X}", i),
+            Section::Table(i) => write!(f, "table {:02X}", i),
+            Section::Instrument(i) => write!(f, "instrument {:02X}", i),
+            Section::Modulator { instrument, modulator } => {
+                write!(f, "instrument {:02X} modulator {}", instrument, modulator + 1)
+            }
+            Section::EffectsSettings => write!(f, "effects settings"),
+            Section::MidiMapping(i) => write!(f, "MIDI mapping {:02X}", i),
+            Section::Scale(i) => write!(f, "scale {:02X}", i),
+            Section::Eq(i) => write!(f, "eq {:02X}", i),
+            Section::Theme => write!(f, "theme"),
+            Section::Scala => write!(f, "Scala file"),
+            Section::MidiChunk(i) => write!(f, "MIDI chunk {}", i),
+        }
+    }
+}
+
+/// Reason why a file could not be parsed, with the location
+/// of the problem.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ParseError {
+    /// The data stops at `offset` while `needed` more bytes
+    /// were expected.
+    Truncated {
+        section: Section,
+        offset: usize,
+        needed: usize,
+    },
+    /// The byte(s) at `offset` hold a value not supported
+    /// for the given field.
+    InvalidValue {
+        section: Section,
+        offset: usize,
+        field: &'static str,
+        value: u32,
+    },
+    /// Malformed text file, the line is 1 based.
+    Syntax {
+        section: Section,
+        line: usize,
+        message: String,
+    },
+    /// The file could not be read at all
+    Io(String),
+}
+
+impl ParseError {
+    /// Part of the file where the error was found
+    pub fn section(&self) -> Section {
+        match self {
+            ParseError::Truncated { section, .. }
+            | ParseError::InvalidValue { section, .. }
+            | ParseError::Syntax { section, .. } => *section,
+            ParseError::Io(_) => Section::File,
+        }
+    }
+
+    /// Absolute byte offset of the error, not available for text files
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            ParseError::Truncated { offset, .. } | ParseError::InvalidValue { offset, .. } => {
+                Some(*offset)
+            }
+            ParseError::Syntax { .. } | ParseError::Io(_) => None,
+        }
+    }
+
+    /// Attach the section being parsed to an error raised by a
+    /// nested reader. Sections are attached from the innermost
+    /// one, so an already known section is kept, a modulator
+    /// only learns its instrument.
+    pub(crate) fn in_section(mut self, outer: Section) -> Self {
+        let section = match &mut self {
+            ParseError::Truncated { section, .. }
+            | ParseError::InvalidValue { section, .. }
+            | ParseError::Syntax { section, .. } => section,
+            ParseError::Io(_) => return self,
+        };
+
+        *section = match (*section, outer) {
+            (Section::File, s) => s,
+            (Section::Modulator { modulator, .. }, Section::Instrument(instrument)) => {
+                Section::Modulator { instrument, modulator }
+            }
+            (s, _) => s,
+        };
+        self
+    }
+}
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "ParseError: {}", &self.0)
+        match self {
+            ParseError::Truncated { section, offset, needed } => write!(
+                f,
+                "ParseError: {}: data truncated at 0x{:X}, {} bytes missing",
+                section, offset, needed
+            ),
+            ParseError::InvalidValue { section, offset, field, value } => write!(
+                f,
+                "ParseError: {}: invalid {} 0x{:X} at 0x{:X}",
+                section, field, value, offset
+            ),
+            ParseError::Syntax { section, line, message } => {
+                write!(f, "ParseError: {} line {}: {}", section, line, message)
+            }
+            ParseError::Io(message) => write!(f, "ParseError: {}", message),
+        }
     }
 }
 
 impl std::error::Error for ParseError {}
 
+/// Element of a song replaced by its default value while
+/// reading in recovery mode, see [`crate::Song::read_lenient`].
+#[derive(PartialEq, Debug, Clone)]
+pub struct Diagnostic {
+    /// Why the element could not be read, its section tells
+    /// which element was replaced.
+    pub error: ParseError,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} replaced by default, {}", self.error.section(), self.error)
+    }
+}
+
+/// How the elements of a file failing to parse are handled
+pub(crate) enum Recovery {
+    /// The first error fails the whole file
+    Strict,
+    /// Failing elements are replaced by a default value. Only the
+    /// first truncation is reported, every following element being
+    /// missing as well.
+    Lenient(Vec<Diagnostic>),
+}
+
+impl Recovery {
+    /// Read an element of the given section, falling back to its
+    /// default in lenient mode.
+    pub(crate) fn read<T>(
+        &mut self,
+        reader: &mut Reader,
+        section: Section,
+        read: impl FnOnce(&mut Reader) -> M8Result<T>,
+        default: impl FnOnce() -> T,
+    ) -> M8Result<T> {
+        let error = match read(reader) {
+            Ok(v) => return Ok(v),
+            Err(e) => e.in_section(section),
+        };
+
+        match self {
+            Recovery::Strict => Err(error),
+            Recovery::Lenient(diagnostics) => {
+                let truncated = |e: &ParseError| matches!(e, ParseError::Truncated { .. });
+                if !truncated(&error) || !diagnostics.iter().any(|d| truncated(&d.error)) {
+                    diagnostics.push(Diagnostic { error });
+                }
+                Ok(default())
+            }
+        }
+    }
+
+    pub(crate) fn diagnostics(self) -> Vec<Diagnostic> {
+        match self {
+            Recovery::Strict => vec![],
+            Recovery::Lenient(diagnostics) => diagnostics,
+        }
+    }
+}
+
 /// Spefic result type for M8 song parsing
 pub type M8Result<T> = std::result::Result<T, ParseError>;
 
+/// Decode a fixed size string field, stopping at the first 0 or 0xFF
+pub(crate) fn decode_string(b: &[u8]) -> String {
+    let mut end = b.iter().position(|&x| x == 0 || x == 255).unwrap_or(b.len());
+
+    while end > 0 {
+        match std::str::from_utf8(&b[0..end]) {
+            Ok(str) => return str.to_string(),
+            Err(_) => end -= 1,
+        }
+    }
+
+    String::from("")
+}
+
 pub struct Reader {
     buffer: Vec<u8>,
     position: usize,
@@ -32,36 +242,42 @@ impl Reader {
         self.buffer.len()
     }
 
-    pub fn read(&mut self) -> u8 {
-        let p: usize = self.position;
-        let b = self.buffer[p];
-        self.position += 1;
-        b
+    /// Load the whole content of a file or stream
+    pub fn from_read(source: &mut impl std::io::Read) -> M8Result<Self> {
+        let mut buffer: Vec<u8> = vec![];
+        source
+            .read_to_end(&mut buffer)
+            .map_err(|e| ParseError::Io(e.to_string()))?;
+        Ok(Self::new(buffer))
     }
 
-    pub fn read_bytes(&mut self, n: usize) -> &[u8] {
-        let p: usize = self.position;
-        let bs = &self.buffer[p..p + n];
-        self.position += n;
-        bs
+    pub fn read(&mut self) -> M8Result<u8> {
+        Ok(self.read_bytes(1)?[0])
     }
 
-    pub fn read_bool(&mut self) -> bool {
-        self.read() == 1
-    }
+    /// Read `n` bytes, failing without moving if the buffer
+    /// is too short.
+    pub fn read_bytes(&mut self, n: usize) -> M8Result<&[u8]> {
+        let p: usize = self.position;
+        let end = p.saturating_add(n);
+        if end > self.buffer.len() {
+            return Err(ParseError::Truncated {
+                section: Section::File,
+                offset: self.buffer.len(),
+                needed: end - self.buffer.len(),
+            });
+        }
 
-    pub fn read_string(&mut self, n: usize) -> String {
-        let b = self.read_bytes(n);
-        let mut end = b.iter().position(|&x| x == 0 || x == 255).unwrap_or(n);
+        self.position = end;
+        Ok(&self.buffer[p..end])
+    }
 
-        while end > 0 {
-            match std::str::from_utf8(&b[0..end]) {
-                Ok(str) => return str.to_string(),
-                Err(_) => end -= 1,
-            }
-        }
+    pub fn read_bool(&mut self) -> M8Result<bool> {
+        Ok(self.read()? == 1)
+    }
 
-        String::from("")
+    pub fn read_string(&mut self, n: usize) -> M8Result<String> {
+        Ok(decode_string(self.read_bytes(n)?))
     }
 
     pub fn pos(&self) -> usize {
@@ -69,10 +285,47 @@ impl Reader {
     }
 
     pub fn skip(&mut self, by: usize) {
-        self.position += by
+        self.position = self.position.saturating_add(by)
     }
 
+    /// Move anywhere, even past the end: the next read reports
+    /// the truncation.
     pub fn set_pos(&mut self, n: usize) {
         self.position = n;
     }
+
+    /// Error for a file shorter than `size` bytes, if it is.
+    pub(crate) fn expect_len(&self, size: usize) -> M8Result<()> {
+        if self.buffer.len() < size {
+            Err(ParseError::Truncated {
+                section: Section::File,
+                offset: self.buffer.len(),
+                needed: size - self.buffer.len(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Error for the byte just read, which is not a valid `field`
+    pub(crate) fn invalid_last(&self, field: &'static str, value: u8) -> ParseError {
+        self.invalid_at(self.position - 1, field, value)
+    }
+
+    /// Error for the byte at `offset`, which is not a valid `field`
+    pub(crate) fn invalid_at(&self, offset: usize, field: &'static str, value: u8) -> ParseError {
+        ParseError::InvalidValue {
+            section: Section::File,
+            offset,
+            field,
+            value: value as u32,
+        }
+    }
+
+    /// Read a byte and convert it, reporting its value and offset
+    /// when it is not a valid `field`.
+    pub(crate) fn read_value<T: TryFrom<u8>>(&mut self, field: &'static str) -> M8Result<T> {
+        let value = self.read()?;
+        T::try_from(value).map_err(|_| self.invalid_last(field, value))
+    }
 }
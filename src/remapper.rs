@@ -4,7 +4,8 @@ use arr_macro::arr;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::{
-    songs::{Song, V4_1_OFFSETS, V4_OFFSETS}, Instrument, Version, FIRMWARE_5_0_SONG_VERSION, FX
+    songs::{Song, SongSteps, Table, V4_1_OFFSETS, V4_OFFSETS}, FxTranslation, Instrument, Version,
+    FIRMWARE_5_0_SONG_VERSION, FX
 };
 
 #[repr(u8)]
@@ -32,6 +33,7 @@ fn make_mapping<const C: usize>(offset: u8) -> [u8; C] {
     arr
 }
 
+#[derive(Clone)]
 pub struct EqMapping {
     /// List all the command ID referencing an EQ as
     /// value. Depend on the song version number.
@@ -86,6 +88,7 @@ impl EqMapping {
 }
 
 /// For every instrument, it's destination instrument
+#[derive(Clone)]
 pub struct InstrumentMapping {
     /// List all the command ID referencing an instrument as
     /// value. Depend on the song version number.
@@ -128,6 +131,7 @@ impl InstrumentMapping {
     }
 }
 
+#[derive(Clone)]
 pub struct TableMapping {
     /// List all the command ID referencing a table as
     /// value. Depend on the song version number.
@@ -173,6 +177,7 @@ impl TableMapping {
     }
 }
 
+#[derive(Clone)]
 pub struct PhraseMapping {
     /// Mapping from the "from" song phrase index to
     /// the "to" phrase index
@@ -212,6 +217,7 @@ impl Default for PhraseMapping {
     }
 }
 
+#[derive(Clone)]
 pub struct ChainMapping {
     pub mapping: [u8; Song::N_CHAINS],
     pub to_move: Vec<u8>,
@@ -246,12 +252,86 @@ impl Default for ChainMapping {
     }
 }
 
+/// Effect without equivalent in the version of the destination
+/// song, removed during the copy.
+#[derive(PartialEq, Debug, Clone)]
+pub struct UntranslatedFx {
+    /// Either a phrase or a table
+    pub kind: MoveKind,
+
+    /// Phrase or table index in the "from" song
+    pub index: usize,
+    pub step: usize,
+    pub fx: FX,
+
+    /// Command name in the "from" song
+    pub command: &'static str,
+}
+
+impl UntranslatedFx {
+    /// Effects of the steps without equivalent, every step comes with
+    /// the instrument playing it if known.
+    fn find<'a, IT>(translation: &FxTranslation, kind: MoveKind, index: usize, steps: IT) -> Vec<Self>
+    where
+        IT: Iterator<Item = ([FX; 3], Option<&'a Instrument>)>,
+    {
+        let mut out = vec![];
+
+        for (step, (fxs, instrument)) in steps.enumerate() {
+            for fx in fxs {
+                if translation.translate_instr(fx, instrument).is_none() {
+                    out.push(UntranslatedFx {
+                        kind,
+                        index,
+                        step,
+                        fx,
+                        command: translation.instr_command_name(fx, instrument).unwrap_or("?"),
+                    })
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// Rectangle of song cells, from `row` and `track` included
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SongRegion {
+    pub row: usize,
+    pub track: usize,
+    pub rows: usize,
+    pub tracks: usize,
+}
+
+impl SongRegion {
+    /// Index in [`SongSteps::steps`] of every cell, row by row
+    fn cells(self) -> impl Iterator<Item = usize> {
+        (self.row..self.row + self.rows).flat_map(move |row| {
+            (self.track..self.track + self.tracks).map(move |track| row * SongSteps::TRACK_COUNT + track)
+        })
+    }
+
+    fn fits(self) -> bool {
+        self.row + self.rows <= SongSteps::ROW_COUNT && self.track + self.tracks <= SongSteps::TRACK_COUNT
+    }
+}
+
+#[derive(Clone)]
 pub struct Remapper {
     pub eq_mapping: EqMapping,
     pub instrument_mapping: InstrumentMapping,
     pub table_mapping: TableMapping,
     pub phrase_mapping: PhraseMapping,
     pub chain_mapping: ChainMapping,
+
+    /// Effect renumbering from the "from" song version to
+    /// the "to" song version
+    pub fx_translation: FxTranslation,
+
+    /// Effects of the copied phrases and tables that do not
+    /// exist in the "to" song version.
+    pub untranslated_fx: Vec<UntranslatedFx>,
 }
 
 /// Iter on all instruments to find allocated Eqs
@@ -297,7 +377,7 @@ fn find_allocated_tables(song: &Song) -> [bool; Song::N_TABLES] {
     allocated_table
 }
 
-fn find_referenced_phrases(song: &Song) -> [bool; Song::N_PHRASES] {
+pub(crate) fn find_referenced_phrases(song: &Song) -> [bool; Song::N_PHRASES] {
     let mut allocated_phrases = arr![false; 255];
     for chain in &song.chains {
         for step in &chain.steps {
@@ -317,7 +397,7 @@ fn find_referenced_phrases(song: &Song) -> [bool; Song::N_PHRASES] {
     allocated_phrases
 }
 
-fn find_referenced_chains(song: &Song) -> [bool; Song::N_CHAINS] {
+pub(crate) fn find_referenced_chains(song: &Song) -> [bool; Song::N_CHAINS] {
     let mut allocated_chains = arr![false; 255];
     for chain in song.song.steps.iter() {
         let chain = *chain as usize;
@@ -336,7 +416,7 @@ fn find_referenced_chains(song: &Song) -> [bool; Song::N_CHAINS] {
 }
 
 /// Try to allocate in the new song by keeping previous numbers
-fn try_allocate(allocation_state: &[bool], previous_id: u8) -> Option<usize> {
+pub(crate) fn try_allocate(allocation_state: &[bool], previous_id: u8) -> Option<usize> {
     let prev = previous_id as usize;
     if !allocation_state[prev] {
         Some(prev)
@@ -376,6 +456,19 @@ pub(crate) const TABLE_TRACKING_COMMAND_NAMES: [&'static str; 2] = ["TBX", "TBL"
 /// These commands track EQs, that must be copied, yada yada.
 pub(crate) const EQ_TRACKING_COMMAND_NAMES: [&'static str; 2] = ["EQI", "EQM"];
 
+/// What the allocation does when the destination song already
+/// holds an identical element.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum ReusePolicy {
+    /// Always copy the elements in free slots
+    AlwaysCopy,
+
+    /// Map onto the identical phrases, chains, instruments (with the
+    /// same table), tables and EQs of the destination.
+    #[default]
+    ReuseIdentical,
+}
+
 /// brief struture to hold structures used to allocate instruments
 struct InstrumentAllocatorState<'a> {
     from_song: &'a Song,
@@ -403,10 +496,16 @@ struct InstrumentAllocatorState<'a> {
     instrument_mapping: InstrumentMapping,
     eq_mapping: EqMapping,
     table_mapping: TableMapping,
+    fx_translation: FxTranslation,
+    reuse: ReusePolicy,
 }
 
 impl<'a> InstrumentAllocatorState<'a> {
-    fn new(from_song: &'a Song, to_song: &'a Song) -> InstrumentAllocatorState<'a> {
+    fn new(
+        from_song: &'a Song,
+        to_song: &'a Song,
+        reuse: ReusePolicy,
+    ) -> InstrumentAllocatorState<'a> {
         let fx_commands_names = crate::FX::fx_command_names(from_song.version);
         let instrument_tracking_commands =
             fx_commands_names.find_indices(&INSTRUMENT_TRACKING_COMMAND_NAMES);
@@ -428,6 +527,8 @@ impl<'a> InstrumentAllocatorState<'a> {
             allocated_tables: find_allocated_tables(to_song),
             instrument_mapping: InstrumentMapping::new(instrument_tracking_commands),
             eq_mapping: EqMapping::default_ver(to_song.version),
+            fx_translation: FxTranslation::new(from_song.version, to_song.version),
+            reuse,
         }
     }
 
@@ -444,8 +545,16 @@ impl<'a> InstrumentAllocatorState<'a> {
             return Ok(());
         }
         // try to find an already exisint Eq with same parameters
-        match self.to_song.eqs.iter().position(|to_eq| to_eq == from_eq) {
+        let existing = match self.reuse {
+            ReusePolicy::AlwaysCopy => None,
+            ReusePolicy::ReuseIdentical => {
+                self.to_song.eqs.iter().position(|to_eq| to_eq == from_eq)
+            }
+        };
+
+        match existing {
             Some(eq_idx) if (eq_idx as usize) < self.eq_mapping.mapping.len() => {
+                self.allocated_eqs[eq_idx] = true;
                 self.eq_mapping.mapping[equ] = eq_idx as u8
             }
             Some(_) | None => match try_allocate_rev(&self.allocated_eqs, equ as u8) {
@@ -475,6 +584,43 @@ impl<'a> InstrumentAllocatorState<'a> {
         self.eq_mapping.eq_tracking_commands.contains(&cmd)
     }
 
+    /// Effects lost in the tables to be moved
+    fn untranslated_table_fx(&self, translation: &FxTranslation) -> Vec<UntranslatedFx> {
+        let moved_tables = self
+            .instrument_mapping
+            .to_move
+            .iter()
+            .chain(self.table_mapping.to_move.iter());
+
+        let mut out = vec![];
+        for table_id in moved_tables {
+            let table_id = *table_id as usize;
+            let instrument = self.from_song.instruments.get(table_id);
+            let steps = self.from_song.tables[table_id].steps.iter().map(|s| (s.all_fx(), instrument));
+            out.extend(UntranslatedFx::find(translation, MoveKind::TBL, table_id, steps));
+        }
+        out
+    }
+
+    /// Table in the destination song as the table would be copied
+    fn copied_table(&self, table_ix: usize) -> Table {
+        self.from_song.tables[table_ix]
+            .map_instr(&self.instrument_mapping, &self.table_mapping, &self.eq_mapping)
+            .translate_fx(&self.fx_translation, self.from_song.instruments.get(table_ix))
+    }
+
+    /// Standalone table of the destination identical to a table,
+    /// empty tables are never shared.
+    fn find_identical_table(&self, table_ix: usize) -> Option<usize> {
+        let table = self.copied_table(table_ix);
+        if self.reuse == ReusePolicy::AlwaysCopy || table.is_empty() {
+            return None;
+        }
+
+        (Song::N_INSTRUMENTS..Song::N_TABLES)
+            .find(|&ix| self.to_song.tables[ix].steps == table.steps)
+    }
+
     fn touch_table(&mut self, table_ix: usize) -> Result<(), String> {
         // out of bound instrument, dont bother or if already allocated
         if table_ix >= Song::N_TABLES || self.table_flags[table_ix] {
@@ -507,14 +653,20 @@ impl<'a> InstrumentAllocatorState<'a> {
 
         // ok so we are not tied to an instruments, we must
         // allocate a slot for ourselves.
-        if table_ix > Song::N_INSTRUMENTS {
-            match try_allocate(&self.allocated_tables, table_ix as u8) {
-                None => return Err(format!("No table slot available")),
-                Some(new_ix) => {
-                    self.table_mapping.to_move.push(table_ix as u8);
-                    self.table_mapping.mapping[table_ix] = new_ix as u8;
-                    self.allocated_tables[new_ix] = true;
+        if table_ix >= Song::N_INSTRUMENTS {
+            match self.find_identical_table(table_ix) {
+                Some(to_ix) => {
+                    self.table_mapping.mapping[table_ix] = to_ix as u8;
+                    self.allocated_tables[to_ix] = true;
                 }
+                None => match try_allocate(&self.allocated_tables, table_ix as u8) {
+                    None => return Err(format!("No table slot available")),
+                    Some(new_ix) => {
+                        self.table_mapping.to_move.push(table_ix as u8);
+                        self.table_mapping.mapping[table_ix] = new_ix as u8;
+                        self.allocated_tables[new_ix] = true;
+                    }
+                },
             }
         }
 
@@ -570,9 +722,22 @@ impl<'a> InstrumentAllocatorState<'a> {
         self.touch_table(instr_ix)?;
 
         self.instrument_flags[instr_ix] = true;
-        match to_song.instruments.iter().position(|i| i == &instr) {
+        let existing = match self.reuse {
+            ReusePolicy::AlwaysCopy => None,
+            ReusePolicy::ReuseIdentical => {
+                let table = self.copied_table(instr_ix);
+                (0..Song::N_INSTRUMENTS).find(|&ix| {
+                    to_song.instruments[ix] == instr && to_song.tables[ix].steps == table.steps
+                })
+            }
+        };
+
+        match existing {
             // horray we have a matching instrument, reuse it
-            Some(to_instr_ix) => self.instrument_mapping.mapping[instr_ix] = to_instr_ix as u8,
+            Some(to_instr_ix) => {
+                self.instrument_mapping.mapping[instr_ix] = to_instr_ix as u8;
+                self.allocated_instruments[to_instr_ix] = true;
+            }
             // no luck, allocate a fresh one
             None => match try_allocate(&self.allocated_instruments, instr_ix as u8) {
                 None => {
@@ -606,6 +771,8 @@ impl Remapper {
             table_mapping: TableMapping::new(table_tracking_commands),
             phrase_mapping: Default::default(),
             chain_mapping: Default::default(),
+            fx_translation: FxTranslation::new(ver, ver),
+            untranslated_fx: vec![],
         }
     }
 
@@ -627,7 +794,14 @@ impl Remapper {
         let phrase = self.phrase_mapping.print();
         let chain = self.chain_mapping.print();
         let table = self.table_mapping.print();
-        format!("{eq}\n{instr}\n{phrase}\n{chain}\n{table}")
+        let mut untranslated = String::new();
+        for fx in &self.untranslated_fx {
+            untranslated = format!(
+                "{untranslated} {:?} {} step {} : {} removed\n",
+                fx.kind, fx.index, fx.step, fx.command
+            );
+        }
+        format!("{eq}\n{instr}\n{phrase}\n{chain}\n{table}\n{untranslated}")
     }
 
     fn allocate_chains<'a, IT>(
@@ -635,6 +809,7 @@ impl Remapper {
         to_song: &Song,
         phrase_mapping: &PhraseMapping,
         from_chains_ids: IT,
+        reuse: ReusePolicy,
     ) -> Result<ChainMapping, String>
     where
         IT: Iterator<Item = &'a u8>,
@@ -653,12 +828,18 @@ impl Remapper {
             seen_chain[chain_id] = true;
             let to_chain = from_song.chains[chain_id].map(phrase_mapping);
 
-            match to_song
-                .chains
-                .iter()
-                .position(|c| c.steps == to_chain.steps)
-            {
-                Some(c) => mapping[chain_id] = c as u8,
+            let existing = match reuse {
+                ReusePolicy::AlwaysCopy => None,
+                ReusePolicy::ReuseIdentical => {
+                    to_song.chains.iter().position(|c| c.steps == to_chain.steps)
+                }
+            };
+
+            match existing {
+                Some(c) => {
+                    allocated_chains[c] = true;
+                    mapping[chain_id] = c as u8
+                }
                 None => match try_allocate(&allocated_chains, chain_id as u8) {
                     None => {
                         return Err(format!(
@@ -678,16 +859,13 @@ impl Remapper {
     }
 
     fn allocate_phrases<'a, IT>(
-        from_song: &Song,
-        to_song: &Song,
-        instr_mapping: &InstrumentMapping,
-        table_mapping: &TableMapping,
-        eq_mapping: &EqMapping,
+        alloc_state: &InstrumentAllocatorState,
         from_chains_ids: IT,
     ) -> Result<PhraseMapping, String>
     where
         IT: Iterator<Item = &'a u8>,
     {
+        let (from_song, to_song) = (alloc_state.from_song, alloc_state.to_song);
         let mut allocated_phrases = find_referenced_phrases(to_song);
 
         let mut seen_phrase: [bool; Song::N_PHRASES] = arr![false; 0xFF];
@@ -706,13 +884,29 @@ impl Remapper {
                 }
 
                 seen_phrase[phrase_ix] = true;
-                let phrase = from_song.phrases[phrase_ix].map_instruments(
-                    instr_mapping,
-                    table_mapping,
-                    eq_mapping,
-                );
-                match to_song.phrases.iter().position(|p| p.steps == phrase.steps) {
-                    Some(known) => phrase_mapping[phrase_ix] = known as u8,
+                let from_phrase = &from_song.phrases[phrase_ix];
+                let phrase = from_phrase
+                    .map_instruments(
+                        &alloc_state.instrument_mapping,
+                        &alloc_state.table_mapping,
+                        &alloc_state.eq_mapping,
+                    )
+                    .translate_fx(
+                        &alloc_state.fx_translation,
+                        &from_phrase.step_instruments(&from_song.instruments),
+                    );
+                let existing = match alloc_state.reuse {
+                    ReusePolicy::AlwaysCopy => None,
+                    ReusePolicy::ReuseIdentical => {
+                        to_song.phrases.iter().position(|p| p.steps == phrase.steps)
+                    }
+                };
+
+                match existing {
+                    Some(known) => {
+                        allocated_phrases[known] = true;
+                        phrase_mapping[phrase_ix] = known as u8
+                    }
                     None => match try_allocate(&allocated_phrases, phrase_ix as u8) {
                         None => {
                             return Err(format!(
@@ -740,11 +934,12 @@ impl Remapper {
         from_song: &'a Song,
         to_song: &'a Song,
         from_chains_ids: IT,
+        reuse: ReusePolicy,
     ) -> Result<InstrumentAllocatorState<'a>, String>
     where
         IT: Iterator<Item = &'a u8>,
     {
-        let mut alloc_state = InstrumentAllocatorState::new(from_song, to_song);
+        let mut alloc_state = InstrumentAllocatorState::new(from_song, to_song, reuse);
 
         for chain_id in from_chains_ids {
             let from_chain = &from_song.chains[*chain_id as usize];
@@ -781,6 +976,20 @@ impl Remapper {
     }
 
     pub fn create<'a, IT>(from_song: &Song, to_song: &Song, chains: IT) -> Result<Remapper, String>
+    where
+        IT: Iterator<Item = &'a u8>,
+    {
+        Remapper::create_with_policy(from_song, to_song, chains, ReusePolicy::ReuseIdentical)
+    }
+
+    /// Same as [`Remapper::create`], telling if the identical elements
+    /// already in `to_song` are reused.
+    pub fn create_with_policy<'a, IT>(
+        from_song: &Song,
+        to_song: &Song,
+        chains: IT,
+        reuse: ReusePolicy,
+    ) -> Result<Remapper, String>
     where
         IT: Iterator<Item = &'a u8>,
     {
@@ -788,19 +997,29 @@ impl Remapper {
 
         // eqs from "from" to "to"
         let alloc_state =
-            Remapper::allocate_eq_and_instruments(from_song, to_song, chain_vec.iter())?;
+            Remapper::allocate_eq_and_instruments(from_song, to_song, chain_vec.iter(), reuse)?;
+
+        let fx_translation = FxTranslation::new(from_song.version, to_song.version);
+        let phrase_mapping = Remapper::allocate_phrases(&alloc_state, chain_vec.iter())?;
 
-        let phrase_mapping = Remapper::allocate_phrases(
+        let chain_mapping = Remapper::allocate_chains(
             from_song,
             to_song,
-            &alloc_state.instrument_mapping,
-            &alloc_state.table_mapping,
-            &alloc_state.eq_mapping,
+            &phrase_mapping,
             chain_vec.iter(),
+            reuse,
         )?;
 
-        let chain_mapping =
-            Remapper::allocate_chains(from_song, to_song, &phrase_mapping, chain_vec.iter())?;
+        let mut untranslated_fx = vec![];
+        for phrase_id in &phrase_mapping.to_move {
+            let phrase_id = *phrase_id as usize;
+            let phrase = &from_song.phrases[phrase_id];
+            let instruments = phrase.step_instruments(&from_song.instruments);
+            let steps = phrase.steps.iter().map(|s| s.all_fx()).zip(instruments);
+            untranslated_fx.extend(UntranslatedFx::find(&fx_translation, MoveKind::PHR, phrase_id, steps));
+        }
+
+        untranslated_fx.extend(alloc_state.untranslated_table_fx(&fx_translation));
 
         Ok(Self {
             eq_mapping: alloc_state.eq_mapping,
@@ -808,9 +1027,95 @@ impl Remapper {
             table_mapping: alloc_state.table_mapping,
             phrase_mapping,
             chain_mapping,
+            fx_translation,
+            untranslated_fx,
+        })
+    }
+
+    /// Mapping to copy instruments without any phrase or chain. The
+    /// table and EQ of every instrument are carried along, as are the
+    /// instruments, tables and EQs reached through their table effects.
+    /// Instruments already present in `to_song` are reused.
+    pub fn create_for_instruments<'a, IT>(
+        from_song: &Song,
+        to_song: &Song,
+        instruments: IT,
+    ) -> Result<Remapper, String>
+    where
+        IT: Iterator<Item = &'a u8>,
+    {
+        let reuse = ReusePolicy::ReuseIdentical;
+        Remapper::create_for_instruments_with_policy(from_song, to_song, instruments, reuse)
+    }
+
+    /// Same as [`Remapper::create_for_instruments`], telling if the
+    /// identical elements already in `to_song` are reused.
+    pub fn create_for_instruments_with_policy<'a, IT>(
+        from_song: &Song,
+        to_song: &Song,
+        instruments: IT,
+        reuse: ReusePolicy,
+    ) -> Result<Remapper, String>
+    where
+        IT: Iterator<Item = &'a u8>,
+    {
+        let mut alloc_state = InstrumentAllocatorState::new(from_song, to_song, reuse);
+        for instrument in instruments {
+            alloc_state.touch_instrument(*instrument as usize)?;
+        }
+
+        let fx_translation = FxTranslation::new(from_song.version, to_song.version);
+        let untranslated_fx = alloc_state.untranslated_table_fx(&fx_translation);
+
+        Ok(Self {
+            eq_mapping: alloc_state.eq_mapping,
+            instrument_mapping: alloc_state.instrument_mapping,
+            table_mapping: alloc_state.table_mapping,
+            phrase_mapping: Default::default(),
+            chain_mapping: Default::default(),
+            fx_translation,
+            untranslated_fx,
         })
     }
 
+    /// Copy the song cells of `region` from `from_song` to `to_song`,
+    /// its first cell landing at `to_row` and `to_track`. The chains
+    /// played in the region and everything they use are allocated and
+    /// copied as with [`Remapper::create`] and [`Remapper::apply`],
+    /// empty cells of the region are copied as well.
+    pub fn copy_song_region(
+        from_song: &Song,
+        to_song: &mut Song,
+        region: SongRegion,
+        to_row: usize,
+        to_track: usize,
+    ) -> Result<Remapper, String> {
+        let destination = SongRegion { row: to_row, track: to_track, ..region };
+        if !region.fits() || !destination.fits() {
+            return Err(format!("Song region {region:?} cannot be copied at row {to_row} track {to_track}"));
+        }
+
+        let chains: Vec<u8> = region
+            .cells()
+            .map(|cell| from_song.song.steps[cell])
+            .filter(|chain| (*chain as usize) < Song::N_CHAINS)
+            .collect();
+
+        let remapper = Remapper::create(from_song, to_song, chains.iter())?;
+        remapper.apply(from_song, to_song);
+
+        for (from_cell, to_cell) in region.cells().zip(destination.cells()) {
+            let chain = from_song.song.steps[from_cell];
+            to_song.song.steps[to_cell] = if (chain as usize) < Song::N_CHAINS {
+                remapper.out_chain(chain)
+            } else {
+                chain
+            };
+        }
+
+        Ok(remapper)
+    }
+
     /// Same as apply but the same song is the source and destination
     pub fn renumber(&self, song: &mut Song) {
         // move eq
@@ -910,11 +1215,9 @@ impl Remapper {
                 }
             }
 
-            to.tables[to_index] = from.tables[instr_id].map_instr(
-                &self.instrument_mapping,
-                &self.table_mapping,
-                &self.eq_mapping,
-            );
+            to.tables[to_index] = from.tables[instr_id]
+                .map_instr(&self.instrument_mapping, &self.table_mapping, &self.eq_mapping)
+                .translate_fx(&self.fx_translation, Some(&from.instruments[instr_id]));
             to.instruments[to_index] = instr;
         }
 
@@ -922,21 +1225,18 @@ impl Remapper {
         for table_id in self.table_mapping.to_move.iter() {
             let table_id = *table_id as usize;
             let to_index = self.table_mapping.mapping[table_id] as usize;
-            to.tables[to_index] = from.tables[table_id].map_instr(
-                &self.instrument_mapping,
-                &self.table_mapping,
-                &self.eq_mapping,
-            );
+            to.tables[to_index] = from.tables[table_id]
+                .map_instr(&self.instrument_mapping, &self.table_mapping, &self.eq_mapping)
+                .translate_fx(&self.fx_translation, from.instruments.get(table_id));
         }
 
         for phrase_id in self.phrase_mapping.to_move.iter() {
             let phrase_id = *phrase_id as usize;
             let to_index = self.phrase_mapping.mapping[phrase_id];
-            to.phrases[to_index as usize] = from.phrases[phrase_id].map_instruments(
-                &self.instrument_mapping,
-                &self.table_mapping,
-                &self.eq_mapping,
-            );
+            let phrase = &from.phrases[phrase_id];
+            to.phrases[to_index as usize] = phrase
+                .map_instruments(&self.instrument_mapping, &self.table_mapping, &self.eq_mapping)
+                .translate_fx(&self.fx_translation, &phrase.step_instruments(&from.instruments));
         }
 
         for chain_id in self.chain_mapping.to_move.iter() {
@@ -949,10 +1249,12 @@ impl Remapper {
 
 #[cfg(test)]
 mod tests {
+    use crate::fx::FX;
     use crate::songs::*;
     use std::fs::File;
 
-    use super::Remapper;
+    use super::{MoveKind, Remapper, ReusePolicy, SongRegion};
+    use crate::Relocation;
 
     fn track_eq() -> Song {
         let mut f = File::open("./examples/songs/TRACKEQ.m8s").expect("Could not open TRACKEQ");
@@ -1010,9 +1312,215 @@ mod tests {
         do_copy(0x30);
     }
 
+    fn song_with_fx(path: &str, fxs: &[&str]) -> Song {
+        let mut f = File::open(path).expect("Could not open song");
+        let mut song = Song::read(&mut f).expect("Could not parse song");
+        let commands = FX::fx_command_names(song.version);
+
+        for (i, name) in fxs.iter().enumerate() {
+            let command = commands.find_indices(&[name])[0];
+            song.phrases[0].steps[i].fx1 = FX { command, value: i as u8 };
+        }
+
+        song.chains[0].steps[0].phrase = 0;
+        song
+    }
+
+    #[test]
+    fn copy_renumbers_old_fx() {
+        let from = song_with_fx("./examples/songs/DEFAULT.m8s", &["RAN", "TPO", "VCH", "IVO"]);
+        let mut to = empty_6();
+        let remap = Remapper::create(&from, &to, [0].iter()).expect("Mapping failure");
+        assert!(remap.untranslated_fx.is_empty());
+        remap.apply(&from, &mut to);
+
+        let commands = FX::fx_command_names(to.version);
+        let phrase = &to.phrases[remap.phrase_mapping.mapping[0] as usize];
+        let names: Vec<_> = phrase.steps[0..4]
+            .iter()
+            .map(|s| commands.try_render(s.fx1.command).unwrap())
+            .collect();
+        assert_eq!(names, ["RND", "TPO", "VCH", "VIN"]);
+        assert_eq!(phrase.steps[3].fx1.value, 3);
+
+        let tpo = FX { command: 0x15, value: 2 };
+        assert_eq!(tpo.translate(from.version, to.version), Some(FX { command: 0x18, value: 2 }));
+    }
+
+    #[test]
+    fn copy_reports_missing_fx() {
+        let from = song_with_fx("./examples/songs/V6_2EMPTY.m8s", &["XMM", "OTT"]);
+        let mut f = File::open("./examples/songs/V4EMPTY.m8s").expect("Could not open V4EMPTY");
+        let mut to = Song::read(&mut f).expect("Could not parse V4EMPTY");
+
+        let remap = Remapper::create(&from, &to, [0].iter()).expect("Mapping failure");
+        assert_eq!(remap.untranslated_fx.len(), 1);
+        assert_eq!(remap.untranslated_fx[0].command, "OTT");
+        assert_eq!(remap.untranslated_fx[0].step, 1);
+        remap.apply(&from, &mut to);
+
+        let commands = FX::fx_command_names(to.version);
+        let phrase = &to.phrases[remap.phrase_mapping.mapping[0] as usize];
+        assert_eq!(commands.try_render(phrase.steps[0].fx1.command), Some("XCM"));
+        assert!(phrase.steps[1].fx1.is_empty());
+    }
+
+    #[test]
+    fn copy_renumbers_instrument_fx() {
+        let mut from = song_with_fx("./examples/songs/TEST-FILE.m8s", &[]);
+        from.chains[0].steps[1].phrase = 0xFF;
+        from.phrases[0] = Phrase::empty(from.version);
+        let steps = &mut from.phrases[0].steps;
+        steps[0].instrument = 5;
+        steps[0].fx1 = FX { command: 0xA6, value: 1 }; // CVO
+        steps[0].fx2 = FX { command: 0x84, value: 2 }; // SHF
+        steps[1].fx1 = FX { command: 0xA7, value: 3 }; // SNC, same instrument
+        from.tables[5] = Table::default_ver(from.version);
+        from.tables[5].steps[0].fx1 = FX { command: 0x84, value: 4 };
+
+        let mut to = empty_6();
+        let remap = Remapper::create(&from, &to, [0].iter()).expect("Mapping failure");
+        let lost: Vec<_> = remap.untranslated_fx.iter().map(|u| (u.kind, u.step, u.command)).collect();
+        assert_eq!(lost, [(MoveKind::PHR, 0, "SHF"), (MoveKind::TBL, 0, "SHF")]);
+        remap.apply(&from, &mut to);
+
+        let phrase = &to.phrases[remap.phrase_mapping.mapping[0] as usize];
+        assert_eq!(phrase.steps[0].fx1, FX { command: 0x84, value: 1 });
+        assert!(phrase.steps[0].fx2.is_empty());
+        assert_eq!(phrase.steps[1].fx1, FX { command: 0xA6, value: 3 });
+
+        let table = &to.tables[remap.instrument_mapping.mapping[5] as usize];
+        assert!(table.steps[0].fx1.is_empty());
+    }
+
     #[test]
     fn copy_chain_40() {
         let remap = do_copy(0x40);
         assert!(remap.table_mapping.to_move.contains(&0x81))
     }
+
+    #[test]
+    fn copy_moves_table_80() {
+        let mut from = song_with_fx("./examples/songs/V6EMPTY.m8s", &["TBX"]);
+        from.phrases[0].steps[0].fx1.value = 0x80;
+        from.tables[0x80].steps[0].velocity = 0x20;
+        let mut to = empty_6();
+        to.tables[0x80].steps[0].velocity = 0x40;
+
+        let remap = Remapper::create(&from, &to, [0].iter()).expect("Mapping failure");
+        assert!(remap.table_mapping.to_move.contains(&0x80));
+        remap.apply(&from, &mut to);
+
+        let to_table = remap.table_mapping.mapping[0x80];
+        assert_ne!(to_table, 0x80);
+        assert_eq!(to.tables[0x80].steps[0].velocity, 0x40);
+        assert_eq!(to.tables[to_table as usize].steps[0].velocity, 0x20);
+        let phrase = &to.phrases[remap.phrase_mapping.mapping[0] as usize];
+        assert_eq!(phrase.steps[0].fx1.value, to_table);
+    }
+
+    #[test]
+    fn copy_song_region() {
+        let mut f = File::open("./examples/songs/Bundle/FDUB3.m8s").expect("Could not open FDUB3");
+        let from = Song::read(&mut f).expect("Could not parse FDUB3");
+        let mut to = empty_6();
+        to.song.steps[0x11 * SongSteps::TRACK_COUNT + 4] = 0x42;
+
+        let region = SongRegion { row: 0, track: 0, rows: 4, tracks: 3 };
+        let remap = Remapper::copy_song_region(&from, &mut to, region, 0x10, 2).expect("Copy failure");
+
+        for row in 0..4 {
+            for track in 0..3 {
+                let from_chain = from.song.steps[row * SongSteps::TRACK_COUNT + track];
+                let to_chain = to.song.steps[(0x10 + row) * SongSteps::TRACK_COUNT + track + 2];
+                if from_chain == 0xFF {
+                    assert_eq!(to_chain, 0xFF);
+                    continue;
+                }
+
+                assert_eq!(to_chain, remap.out_chain(from_chain));
+                let steps = from.chains[from_chain as usize].steps.iter();
+                for (from_step, to_step) in steps.zip(to.chains[to_chain as usize].steps.iter()) {
+                    if from_step.phrase == 0xFF {
+                        continue;
+                    }
+                    let from_phrase = &from.phrases[from_step.phrase as usize];
+                    let to_phrase = &to.phrases[to_step.phrase as usize];
+                    let notes = |p: &Phrase| p.steps.iter().map(|s| s.note).collect::<Vec<_>>();
+                    assert_eq!(notes(from_phrase), notes(to_phrase));
+                }
+            }
+        }
+
+        assert!(!remap.instrument_mapping.to_move.is_empty());
+        assert_eq!(to.song.steps[..0x10 * SongSteps::TRACK_COUNT], [0xFF; 0x80]);
+        assert_eq!(to.song.steps[0x10 * SongSteps::TRACK_COUNT + 1], 0xFF);
+
+        let outside = SongRegion { row: 0xFE, track: 0, rows: 4, tracks: 1 };
+        assert!(Remapper::copy_song_region(&from, &mut to, outside, 0, 0).is_err());
+        assert!(Remapper::copy_song_region(&from, &mut to, region, 0, 6).is_err());
+    }
+
+    #[test]
+    fn copy_instruments() {
+        let mut from = track_eq();
+        let nxt = FX::fx_command_names(from.version).find_indices(&["NXT"])[0];
+        from.tables[1].steps[0].fx1 = FX { command: nxt, value: 2 };
+        let mut to = empty_6();
+        to.instruments[1] = from.instruments[0].clone();
+
+        let remap = Remapper::create_for_instruments(&from, &to, [1].iter()).expect("Mapping failure");
+        let moved = &remap.instrument_mapping.to_move;
+        assert!(moved.contains(&1) && moved.contains(&2));
+        assert!(remap.phrase_mapping.to_move.is_empty() && remap.chain_mapping.to_move.is_empty());
+        remap.apply(&from, &mut to);
+
+        let to_instr = remap.instrument_mapping.mapping[1] as usize;
+        let to_next = remap.instrument_mapping.mapping[2];
+        assert_ne!(to_instr, 1);
+        assert_eq!(to.instruments[to_instr].name(), from.instruments[1].name());
+        assert_eq!(to.tables[to_instr].steps[0].fx1, FX { command: nxt, value: to_next });
+        let to_eq = to.instruments[to_instr].equ().unwrap() as usize;
+        assert!(to.eqs[to_eq] == from.eqs[from.instruments[1].equ().unwrap() as usize]);
+
+        // a second copy reuses the instruments
+        let again = Remapper::create_for_instruments(&from, &to, [1].iter()).expect("Mapping failure");
+        assert!(again.instrument_mapping.to_move.is_empty());
+        assert_eq!(again.instrument_mapping.mapping[1] as usize, to_instr);
+
+        let always_copy = ReusePolicy::AlwaysCopy;
+        let copy = Remapper::create_for_instruments_with_policy(&from, &to, [1].iter(), always_copy)
+            .expect("Mapping failure");
+        assert!(copy.instrument_mapping.to_move.contains(&1));
+        assert_ne!(copy.instrument_mapping.mapping[1] as usize, to_instr);
+    }
+
+    #[test]
+    fn repeated_copy_reuses_content() {
+        let from = track_eq();
+        let mut to = empty_6();
+        let first = Remapper::create(&from, &to, [0x40].iter()).expect("Mapping failure");
+        first.apply(&from, &mut to);
+        assert!(first.table_mapping.to_move.contains(&0x81));
+
+        let reuse = ReusePolicy::ReuseIdentical;
+        let again = Remapper::create_with_policy(&from, &to, [0x40].iter(), reuse)
+            .expect("Mapping failure");
+        let mut moves: Vec<Relocation> = vec![];
+        again.describe(&mut moves);
+        assert!(moves.is_empty());
+        assert_eq!(again.out_chain(0x40), first.out_chain(0x40));
+        assert_eq!(again.table_mapping.mapping[0x81], first.table_mapping.mapping[0x81]);
+
+        let copy = Remapper::create_with_policy(&from, &to, [0x40].iter(), ReusePolicy::AlwaysCopy)
+            .expect("Mapping failure");
+        assert_ne!(copy.out_chain(0x40), first.out_chain(0x40));
+        assert!(copy.table_mapping.to_move.contains(&0x81));
+        assert_ne!(copy.table_mapping.mapping[0x81], first.table_mapping.mapping[0x81]);
+        let instrument = first.instrument_mapping.to_move[0] as usize;
+        assert_ne!(
+            copy.instrument_mapping.mapping[instrument],
+            first.instrument_mapping.mapping[instrument]
+        );
+    }
 }
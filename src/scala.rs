@@ -0,0 +1,586 @@
+//! Conversion between M8 scales and Scala tuning files (`.scl`
+//! scales and `.kbm` keyboard mappings).
+use crate::reader::*;
+use crate::scale::*;
+
+use arr_macro::arr;
+
+/// Number of keys in an octave of the M8 scale editor
+const OCTAVE_KEYS: usize = 12;
+
+/// Biggest offset that can be stored for a note, in cents
+const MAX_OFFSET_CENTS: f64 = 2400.0;
+
+/// Maximum length of a scale name
+const NAME_LENGTH: usize = 16;
+
+/// Part of a Scala tuning that could not be represented in
+/// the imported scale.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ScalaLoss {
+    /// Offset of the note has been rounded to the nearest cent
+    CentRounding { note: usize, wanted: f64, stored: i32 },
+
+    /// Offset of the note is above 24 semitones (in either
+    /// direction) and has been clamped.
+    OutOfRange { note: usize, wanted: f64, stored: i32 },
+
+    /// Scale degree without a key to play it, it has been dropped
+    UnmappedDegree { degree: usize, cents: f64 },
+
+    /// The scale repeats on something else than an octave, only
+    /// the first period is imported.
+    NonOctavePeriod { cents: f64 },
+
+    /// Description was too long to be used as a scale name
+    NameTruncated { name: String },
+}
+
+/// Result of a Scala import, with everything that could not
+/// be kept.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ScalaImport {
+    pub scale: Scale,
+    pub losses: Vec<ScalaLoss>,
+}
+
+/// Content of a `.scl` file
+struct ScalaScale {
+    description: String,
+
+    /// Pitch of every degree in cents, the tonic is not included
+    /// and the last one is the period of the scale.
+    pitches: Vec<f64>,
+}
+
+impl ScalaScale {
+    fn parse(scl: &str) -> M8Result<ScalaScale> {
+        let end = end_line(scl);
+        let mut lines = numbered_lines(scl).filter(|(_, l)| !l.starts_with('!'));
+
+        let description = lines
+            .next()
+            .ok_or_else(|| syntax_error(end, "Missing Scala description".to_string()))?
+            .1
+            .trim()
+            .to_string();
+
+        let (count_number, count_line) = lines
+            .next()
+            .ok_or_else(|| syntax_error(end, "Missing Scala note count".to_string()))?;
+        let count: usize = first_word(count_line).parse().map_err(|_| {
+            syntax_error(count_number, format!("Invalid Scala note count '{}'", count_line.trim()))
+        })?;
+
+        if count > OCTAVE_KEYS {
+            return Err(syntax_error(
+                count_number,
+                format!("Scala scale has {} notes, at most {} can be imported", count, OCTAVE_KEYS),
+            ));
+        }
+
+        let pitches = lines
+            .take(count)
+            .map(|(number, line)| parse_pitch(number, line))
+            .collect::<M8Result<Vec<f64>>>()?;
+
+        if pitches.len() != count {
+            return Err(syntax_error(
+                end,
+                format!("Scala scale announces {} notes but only has {}", count, pitches.len()),
+            ));
+        }
+
+        Ok(ScalaScale { description, pitches })
+    }
+
+    /// Number of different degrees, including the tonic
+    fn degree_count(&self) -> usize {
+        self.pitches.len().max(1)
+    }
+
+    fn period(&self) -> f64 {
+        self.pitches.last().copied().unwrap_or(1200.0)
+    }
+
+    /// Pitch in cents of any degree, going through periods
+    /// for degrees outside of the first one.
+    fn degree_pitch(&self, degree: i64) -> f64 {
+        let size = self.pitches.len() as i64;
+        if size == 0 {
+            return 0.0;
+        }
+
+        let period = degree.div_euclid(size);
+        let index = degree.rem_euclid(size) as usize;
+        let base = if index == 0 { 0.0 } else { self.pitches[index - 1] };
+        base + period as f64 * self.period()
+    }
+}
+
+/// Lines of a text file along with their 1 based number
+fn numbered_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines().enumerate().map(|(i, l)| (i + 1, l))
+}
+
+/// Line number reported for missing content
+fn end_line(text: &str) -> usize {
+    text.lines().count() + 1
+}
+
+fn syntax_error(line: usize, message: String) -> ParseError {
+    ParseError::Syntax { section: Section::Scala, line, message }
+}
+
+/// First whitespace separated word of a line, Scala allows
+/// anything after the value.
+fn first_word(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
+/// Convert a Scala pitch (cents if it has a dot, ratio otherwise)
+/// in cents.
+fn parse_pitch(number: usize, line: &str) -> M8Result<f64> {
+    let word = first_word(line);
+    let invalid = || syntax_error(number, format!("Invalid Scala pitch '{}'", line.trim()));
+
+    if word.contains('.') {
+        return word.parse::<f64>().map_err(|_| invalid());
+    }
+
+    let (num, den) = match word.split_once('/') {
+        Some((num, den)) => (num, den),
+        None => (word, "1"),
+    };
+
+    let num: f64 = num.parse().map_err(|_| invalid())?;
+    let den: f64 = den.parse().map_err(|_| invalid())?;
+    if num <= 0.0 || den <= 0.0 {
+        return Err(invalid());
+    }
+
+    Ok(1200.0 * (num / den).log2())
+}
+
+/// Scale degrees played by the keys of an octave
+struct KeyboardMapping {
+    /// Key of the octave playing the tonic, 0 being C
+    middle_key: usize,
+
+    /// Degree played one octave above the tonic
+    octave_degree: i64,
+
+    /// Degree played by each key, starting from the middle key
+    degrees: Vec<Option<i64>>,
+}
+
+impl KeyboardMapping {
+    /// Pitch in cents played by the key of the `index`th entry,
+    /// relative to the equal tempered pitch of that key. Entries
+    /// past C are played one octave lower, on the keys before the
+    /// middle one.
+    fn offset(&self, scl: &ScalaScale, index: usize, degree: i64) -> f64 {
+        let pitch = if self.middle_key + index >= OCTAVE_KEYS {
+            scl.degree_pitch(degree - self.octave_degree) + 1200.0
+        } else {
+            scl.degree_pitch(degree)
+        };
+
+        pitch - (index * 100) as f64
+    }
+
+    fn key(&self, index: usize) -> usize {
+        (self.middle_key + index) % OCTAVE_KEYS
+    }
+}
+
+/// Parse a `.kbm` file mapping 12 keys (or a linear mapping)
+fn parse_keyboard_mapping(kbm: &str) -> M8Result<KeyboardMapping> {
+    let end = end_line(kbm);
+    let mut lines =
+        numbered_lines(kbm).filter(|(_, l)| !l.starts_with('!') && !l.trim().is_empty());
+
+    let mut header = [0i64; 7];
+    let (mut size_number, mut octave_number) = (end, end);
+    for (i, field) in header.iter_mut().enumerate() {
+        let (number, line) = lines
+            .next()
+            .ok_or_else(|| syntax_error(end, "Keyboard mapping header is incomplete".to_string()))?;
+
+        match i {
+            0 => size_number = number,
+            6 => octave_number = number,
+            _ => {}
+        }
+
+        // the reference frequency is the only non integer field
+        *field = if i == 5 {
+            0
+        } else {
+            first_word(line).parse().map_err(|_| {
+                syntax_error(number, format!("Invalid keyboard mapping value '{}'", line.trim()))
+            })?
+        };
+    }
+
+    let middle_key = header[3].rem_euclid(OCTAVE_KEYS as i64) as usize;
+    let map_size = header[0];
+    if map_size == 0 {
+        return Ok(KeyboardMapping {
+            middle_key,
+            octave_degree: OCTAVE_KEYS as i64,
+            degrees: (0..OCTAVE_KEYS as i64).map(Some).collect(),
+        });
+    }
+
+    if map_size != OCTAVE_KEYS as i64 {
+        return Err(syntax_error(
+            size_number,
+            format!(
+                "Keyboard mapping covers {} keys, only {} keys mappings can be imported",
+                map_size, OCTAVE_KEYS
+            ),
+        ));
+    }
+
+    let mut mapping: Vec<Option<i64>> = lines
+        .take(OCTAVE_KEYS)
+        .map(|(number, line)| match first_word(line) {
+            "x" | "X" => Ok(None),
+            w => w.parse().map(Some).map_err(|_| {
+                syntax_error(number, format!("Invalid keyboard mapping entry '{}'", line.trim()))
+            }),
+        })
+        .collect::<M8Result<Vec<Option<i64>>>>()?;
+
+    // missing entries are unmapped keys
+    mapping.resize(OCTAVE_KEYS, None);
+
+    let octave_degree = header[6];
+    if octave_degree <= 0 {
+        return Err(syntax_error(
+            octave_number,
+            format!("Invalid keyboard mapping formal octave degree {}", octave_degree),
+        ));
+    }
+
+    Ok(KeyboardMapping { middle_key, octave_degree, degrees: mapping })
+}
+
+/// Keys of the octave for a scale without keyboard mapping, every
+/// degree goes on the nearest key still free.
+fn nearest_key_mapping(scl: &ScalaScale) -> KeyboardMapping {
+    let mut mapping = vec![None; OCTAVE_KEYS];
+
+    for degree in 0..scl.degree_count() {
+        let cents = scl.degree_pitch(degree as i64);
+        let key = (cents / 100.0).round().clamp(0.0, (OCTAVE_KEYS - 1) as f64) as usize;
+
+        let free_key = (key..OCTAVE_KEYS)
+            .chain((0..key).rev())
+            .find(|&k| mapping[k].is_none());
+
+        if let Some(k) = free_key {
+            mapping[k] = Some(degree as i64);
+        }
+    }
+
+    KeyboardMapping {
+        middle_key: 0,
+        octave_degree: scl.pitches.len() as i64,
+        degrees: mapping,
+    }
+}
+
+impl Scale {
+    /// Import a Scala scale of at most 12 notes, with an optional
+    /// keyboard mapping of 12 keys.
+    ///
+    /// Without keyboard mapping each degree is placed on the nearest
+    /// key, starting from C. A keyboard mapping places the tonic on
+    /// its middle note, at its equal tempered pitch. Keys not playing
+    /// any degree are disabled. Everything that could not be
+    /// represented is listed in the losses.
+    pub fn from_scala(number: u8, scl: &str, kbm: Option<&str>) -> M8Result<ScalaImport> {
+        let scala = ScalaScale::parse(scl)?;
+        let mut losses = vec![];
+
+        let mapping = match kbm {
+            Some(kbm) => parse_keyboard_mapping(kbm)?,
+            None => nearest_key_mapping(&scala),
+        };
+
+        // the M8 repeats the scale every octave, whatever the
+        // mapping formal octave is
+        let period = scala.degree_pitch(mapping.octave_degree);
+        if !scala.pitches.is_empty() && (period - 1200.0).abs() > 0.005 {
+            losses.push(ScalaLoss::NonOctavePeriod { cents: period });
+        }
+
+        let degree_count = scala.degree_count() as i64;
+        for degree in 0..degree_count {
+            let played = mapping
+                .degrees
+                .iter()
+                .any(|d| d.is_some_and(|d| d.rem_euclid(degree_count) == degree));
+
+            if !played {
+                losses.push(ScalaLoss::UnmappedDegree {
+                    degree: degree as usize,
+                    cents: scala.degree_pitch(degree),
+                });
+            }
+        }
+
+        let mut notes = arr![NoteOffset { enabled: false, semitones: 0.0 }; 12];
+        for (index, degree) in mapping.degrees.iter().enumerate() {
+            let Some(degree) = degree else { continue };
+
+            let key = mapping.key(index);
+            let wanted = mapping.offset(&scala, index, *degree);
+            let clamped = wanted.clamp(-MAX_OFFSET_CENTS, MAX_OFFSET_CENTS);
+            let stored = clamped.round() as i32;
+
+            if clamped != wanted {
+                losses.push(ScalaLoss::OutOfRange { note: key, wanted, stored });
+            } else if (wanted - f64::from(stored)).abs() > 0.005 {
+                losses.push(ScalaLoss::CentRounding { note: key, wanted, stored });
+            }
+
+            notes[key] = NoteOffset::from_cents(true, stored);
+        }
+
+        let mut name = scala.description.clone();
+        if name.len() > NAME_LENGTH {
+            losses.push(ScalaLoss::NameTruncated { name: name.clone() });
+            let mut end = NAME_LENGTH;
+            while !name.is_char_boundary(end) {
+                end -= 1;
+            }
+            name.truncate(end);
+        }
+
+        Ok(ScalaImport {
+            scale: Scale { number, name, notes },
+            losses,
+        })
+    }
+
+    /// Export the scale as a Scala `.scl` file, C being the tonic.
+    ///
+    /// Disabled notes are left out of the scale and listed in
+    /// comments.
+    pub fn to_scala(&self) -> String {
+        let mut scl = format!("! {}.scl\n!\n", self.name);
+        for (note, offset) in self.notes.iter().enumerate() {
+            if !offset.enabled {
+                scl.push_str(&format!("! {} disabled\n", NOTE_NAMES[note]));
+            }
+        }
+
+        if self.notes[0].enabled && self.notes[0].cents() != 0 {
+            scl.push_str(&format!("! C offset of {} cents ignored\n", self.notes[0].cents()));
+        }
+
+        let pitches: Vec<i32> = self
+            .notes
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, offset)| offset.enabled)
+            .map(|(note, offset)| note as i32 * 100 + offset.cents())
+            .collect();
+
+        scl.push_str(&format!("{}\n {}\n!\n", self.name, pitches.len() + 1));
+        for cents in pitches {
+            scl.push_str(&format!(" {}.0\n", cents));
+        }
+        scl.push_str(" 2/1\n");
+        scl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scala::*;
+
+    const MEANTONE: &str = "! meantone.scl
+!
+1/4-comma meantone
+ 12
+!
+ 76.04900
+ 193.15686
+ 310.26471
+ 5/4
+ 503.42157
+ 579.47057
+ 696.57843
+ 25/16
+ 889.73529
+ 1006.84314
+ 1082.89214
+ 2/1
+";
+
+    #[test]
+    fn test_twelve_notes_import() {
+        let imported = Scale::from_scala(3, MEANTONE, None).expect("Could not import scale");
+        let scale = &imported.scale;
+
+        assert_eq!(scale.number, 3);
+        assert_eq!(scale.name, "1/4-comma meanto");
+        assert!(scale.notes.iter().all(|n| n.enabled));
+        assert_eq!(scale.notes[0].cents(), 0);
+        assert_eq!(scale.notes[1].cents(), -24);
+        assert_eq!(scale.notes[4].cents(), -14);
+        assert_eq!(scale.notes[7].cents(), -3);
+        assert_eq!(scale.notes[1].to_bytes(), (0xFF, 76));
+
+        assert!(imported.losses.contains(&ScalaLoss::NameTruncated {
+            name: "1/4-comma meantone".to_string()
+        }));
+        assert!(imported
+            .losses
+            .iter()
+            .any(|l| matches!(l, ScalaLoss::CentRounding { note: 1, stored: -24, .. })));
+        assert!(!imported
+            .losses
+            .iter()
+            .any(|l| matches!(l, ScalaLoss::CentRounding { note: 0, .. })));
+    }
+
+    #[test]
+    fn test_pentatonic_nearest_keys() {
+        let scl = "SLENDRO\n5\n240.0\n480.0\n720.0\n960.0\n2/1\n";
+        let imported = Scale::from_scala(0, scl, None).expect("Could not import scale");
+        let enabled: Vec<usize> = (0..12).filter(|&i| imported.scale.notes[i].enabled).collect();
+
+        assert_eq!(enabled, vec![0, 2, 5, 7, 10]);
+        assert_eq!(imported.scale.notes[2].cents(), 40);
+        assert_eq!(imported.scale.notes[5].cents(), -20);
+        assert!(imported.losses.is_empty());
+    }
+
+    #[test]
+    fn test_keyboard_mapping() {
+        let scl = "SLENDRO\n5\n240.0\n480.0\n720.0\n960.0\n2/1\n";
+        let kbm = "! slendro.kbm
+12
+0
+127
+60
+60
+261.625565
+5
+0
+x
+1
+x
+2
+x
+x
+3
+x
+4
+x
+5
+";
+        let imported = Scale::from_scala(0, scl, Some(kbm)).expect("Could not import scale");
+        let enabled: Vec<usize> = (0..12).filter(|&i| imported.scale.notes[i].enabled).collect();
+
+        assert_eq!(enabled, vec![0, 2, 4, 7, 9, 11]);
+        assert_eq!(imported.scale.notes[4].cents(), 80);
+        assert_eq!(imported.scale.notes[11].cents(), 100);
+        assert!(imported.losses.is_empty());
+
+        let partial = kbm.replace("\n4\nx\n5\n", "\nx\nx\n5\n");
+        let imported = Scale::from_scala(0, scl, Some(&partial)).expect("Could not import scale");
+        assert_eq!(imported.losses, vec![ScalaLoss::UnmappedDegree { degree: 4, cents: 960.0 }]);
+    }
+
+    #[test]
+    fn test_keyboard_mapping_middle_note() {
+        let scl = "SLENDRO\n5\n240.0\n480.0\n720.0\n960.0\n2/1\n";
+        let kbm = "12\n0\n127\n62\n62\n293.66\n5\n0\nx\n1\nx\n2\nx\nx\n3\nx\n4\nx\n5\n";
+        let imported = Scale::from_scala(0, scl, Some(kbm)).expect("Could not import scale");
+        let enabled: Vec<usize> = (0..12).filter(|&i| imported.scale.notes[i].enabled).collect();
+
+        // tonic on D, degree 5 played by the C# below it
+        assert_eq!(enabled, vec![1, 2, 4, 6, 9, 11]);
+        assert_eq!(imported.scale.notes[2].cents(), 0);
+        assert_eq!(imported.scale.notes[6].cents(), 80);
+        assert_eq!(imported.scale.notes[1].cents(), 100);
+        assert!(imported.losses.is_empty());
+
+        let linear = "0\n0\n127\n65\n65\n349.23\n0\n";
+        let imported = Scale::from_scala(0, scl, Some(linear)).expect("Could not import scale");
+        assert_eq!(imported.scale.notes[5].cents(), 0);
+        assert_eq!(imported.scale.notes[6].cents(), 140);
+        assert_eq!(imported.scale.notes[4].cents(), -140);
+
+        let wide_octave = kbm.replace("\n5\n0\nx\n", "\n4\n0\nx\n");
+        let imported = Scale::from_scala(0, scl, Some(&wide_octave)).expect("Could not import scale");
+        assert!(imported.losses.contains(&ScalaLoss::NonOctavePeriod { cents: 960.0 }));
+
+        let no_octave = kbm.replace("\n5\n0\nx\n", "\n0\n0\nx\n");
+        assert!(Scale::from_scala(0, scl, Some(&no_octave)).is_err());
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let scl = "WIDE\n2\n3000.0\n2/1\n";
+        let kbm = "12\n0\n127\n60\n60\n440.0\n2\n0\n1\n";
+        let imported = Scale::from_scala(0, scl, Some(kbm)).expect("Could not import scale");
+
+        assert_eq!(imported.scale.notes[1].cents(), 2400);
+        assert!(!imported.scale.notes[2].enabled);
+        assert!(imported.losses.contains(&ScalaLoss::OutOfRange {
+            note: 1,
+            wanted: 2900.0,
+            stored: 2400
+        }));
+        assert_eq!(imported.losses.len(), 1);
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let mut f = std::fs::File::open("./examples/songs/V6_6EMPTY.m8s").expect("Could not open song");
+        let song = crate::Song::read(&mut f).expect("Could not parse song");
+        for scale in &song.scales {
+            let imported = Scale::from_scala(scale.number, &scale.to_scala(), None)
+                .expect("Could not import exported scale");
+            assert_eq!(&imported.scale, scale);
+            assert!(imported.losses.is_empty());
+        }
+
+        let imported = Scale::from_scala(0, MEANTONE, None).expect("Could not import scale");
+        let exported = imported.scale.to_scala();
+        assert!(exported.contains("\n 12\n"));
+        assert!(exported.contains("\n 76.0\n"));
+        let reimported = Scale::from_scala(0, &exported, None).expect("Could not import exported scale");
+        assert_eq!(reimported.scale, imported.scale);
+
+        let major = &song.scales[1];
+        let exported = major.to_scala();
+        assert!(exported.contains("! C# disabled\n"));
+        assert!(exported.contains("MAJOR\n 7\n"));
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        assert!(Scale::from_scala(0, "TOO BIG\n13\n", None).is_err());
+        assert!(Scale::from_scala(0, "SHORT\n3\n100.0\n", None).is_err());
+        assert!(Scale::from_scala(0, "BAD\n1\nabc\n", None).is_err());
+
+        let err = Scale::from_scala(0, "! bad.scl\nBAD\n1\nabc\n", None).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Syntax {
+                section: Section::Scala,
+                line: 4,
+                message: "Invalid Scala pitch 'abc'".to_string()
+            }
+        );
+    }
+}
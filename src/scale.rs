@@ -1,11 +1,37 @@
 use crate::reader::*;
 use crate::version::*;
+use crate::writer::Writer;
 
 use std::fmt;
 
 use arr_macro::arr;
 use byteorder::{ByteOrder, LittleEndian};
 
+/// Name of the notes of an octave, from C
+pub(crate) const NOTE_NAMES: [&str; 12] =
+    ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// Name and enabled notes (bit 0 for C) of the scales found
+/// in a new song.
+const FACTORY_SCALES: [(&str, u16); 16] = [
+    ("CHROMATIC", 0xFFF),
+    ("MAJOR", 0xAB5),
+    ("MINOR", 0x5AD),
+    ("DORIAN", 0x6AD),
+    ("LYDIAN", 0xAD5),
+    ("MIXOLYDIAN", 0x6B5),
+    ("LOCRIAN", 0x56B),
+    ("PENTATONIC", 0x295),
+    ("MINOR PENTATONIC", 0x4A9),
+    ("MAJOR BLUES", 0x29D),
+    ("MINOR BLUES", 0x4E9),
+    ("ROMANIAN MINOR", 0x6CD),
+    ("HIRAJOSHI", 0x18D),
+    ("KUMOIJOSHI", 0x1A3),
+    ("IN-SEN", 0x4A3),
+    ("IWATO", 0x463),
+];
+
 #[derive(PartialEq, Clone)]
 pub struct Scale {
     pub number: u8,
@@ -16,38 +42,93 @@ pub struct Scale {
 impl Scale {
     const SIZE: usize = 32;
 
-    pub fn read(reader: &mut impl std::io::Read) -> M8Result<Self> {
-        let mut buf: Vec<u8> = vec![];
-        reader.read_to_end(&mut buf).unwrap();
-        let len = buf.len();
-        let mut reader = Reader::new(buf);
-
-        if len < Self::SIZE + Version::SIZE {
-            return Err(ParseError(
-                "File is not long enough to be a M8 Scale".to_string(),
-            ));
+    /// Size of a written scale: note mask, note offsets and name
+    pub const V4_SIZE: usize = 2 + 12 * 2 + 16;
+
+    /// From firmware 4.0 onward, each scale of a song is followed
+    /// by 4 extra bytes.
+    const V4_SONG_PADDING: usize = 4;
+
+    /// Distance between two consecutive scales in a song file
+    pub(crate) fn song_stride(version: Version) -> usize {
+        if version.after(&FIRMWARE_4_0_SONG_VERSION) {
+            Scale::V4_SIZE + Scale::V4_SONG_PADDING
+        } else {
+            Scale::V4_SIZE
+        }
+    }
+
+    /// Scale stored at a given slot of a new song
+    pub fn factory(number: u8) -> Scale {
+        let (name, map) = FACTORY_SCALES[number as usize % FACTORY_SCALES.len()];
+        let mut notes = arr![NoteOffset::default(); 12];
+
+        for (i, note) in notes.iter_mut().enumerate() {
+            note.enabled = ((map >> i) & 0x1) == 1;
+        }
+
+        Self {
+            number,
+            name: name.to_string(),
+            notes,
         }
+    }
+
+    pub fn read(reader: &mut impl std::io::Read) -> M8Result<Self> {
+        let mut reader = Reader::from_read(reader)?;
+
+        reader.expect_len(Self::SIZE + Version::SIZE)?;
         Version::from_reader(&mut reader)?;
         Self::from_reader(&mut reader, 0)
     }
 
     pub(crate) fn from_reader(reader: &mut Reader, number: u8) -> M8Result<Self> {
-        let map = LittleEndian::read_u16(reader.read_bytes(2));
+        let map = LittleEndian::read_u16(reader.read_bytes(2)?);
         let mut notes = arr![NoteOffset::default(); 12];
 
         for (i, note) in notes.iter_mut().enumerate() {
             note.enabled = ((map >> i) & 0x1) == 1;
-            let offset = f32::from(reader.read()) + (f32::from(reader.read()) / 100.0);
-            note.semitones = offset;
+            let semitones = reader.read()?;
+            let cents = reader.read()?;
+            note.semitones = NoteOffset::from_bytes(semitones, cents);
         }
 
-        let name = reader.read_string(16);
+        let name = reader.read_string(16)?;
         Ok(Self {
             number,
             name,
             notes,
         })
     }
+
+    pub fn write(&self, w: &mut Writer) {
+        let mut map = 0;
+        for (i, note) in self.notes.iter().enumerate() {
+            if note.enabled {
+                map |= 1 << i;
+            }
+        }
+
+        let mut map_bytes = [0; 2];
+        LittleEndian::write_u16(&mut map_bytes, map);
+        w.write_bytes(&map_bytes);
+
+        for note in &self.notes {
+            let (semitones, cents) = note.to_bytes();
+            w.write(semitones);
+            w.write(cents);
+        }
+
+        w.write_string(&self.name, 16);
+    }
+
+    /// Write the scale as a standalone scale file for the given
+    /// version, to be used with a writer from [`Writer::new_scale_writer`].
+    pub fn write_file(&self, version: Version, w: &mut Writer) {
+        w.seek(0);
+        version.write_tagged(w, Version::SCALE_FILE_TAG);
+        self.write(w);
+    }
 }
 
 impl Default for Scale {
@@ -62,13 +143,10 @@ impl Default for Scale {
 
 impl fmt::Display for Scale {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let notes = vec![
-            "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
-        ];
         let offsets = self
             .notes
             .iter()
-            .zip(notes.iter())
+            .zip(NOTE_NAMES.iter())
             .map(|(offset, note)| -> String {
                 let s = if offset.enabled {
                     let sign = if offset.semitones < 0.0 { "-" } else { " " };
@@ -106,4 +184,33 @@ impl NoteOffset {
             semitones: 0.0,
         }
     }
+
+    /// Offset from a number of cents, using the same precision
+    /// as the one read from a file.
+    pub fn from_cents(enabled: bool, cents: i32) -> Self {
+        Self {
+            enabled,
+            semitones: NoteOffset::from_bytes(
+                cents.div_euclid(100) as i8 as u8,
+                cents.rem_euclid(100) as u8,
+            ),
+        }
+    }
+
+    /// Offset rounded to the nearest cent
+    pub fn cents(&self) -> i32 {
+        (self.semitones * 100.0).round() as i32
+    }
+
+    /// Semitones are stored signed, and cents are always
+    /// going upward from them.
+    fn from_bytes(semitones: u8, cents: u8) -> f32 {
+        f32::from(semitones as i8) + (f32::from(cents) / 100.0)
+    }
+
+    /// Split the offset in its (semitones, cents) stored representation
+    pub fn to_bytes(&self) -> (u8, u8) {
+        let total_cents = self.cents();
+        (total_cents.div_euclid(100) as i8 as u8, total_cents.rem_euclid(100) as u8)
+    }
 }
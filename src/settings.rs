@@ -1,6 +1,6 @@
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
-use crate::{reader::*, Version, FIRMWARE_4_0_SONG_VERSION, FIRMWARE_6_0_SONG_VERSION, FIRMWARE_6_2_SONG_VERSION};
+use crate::{reader::*, writer::Writer, Version, FIRMWARE_4_0_SONG_VERSION, FIRMWARE_6_0_SONG_VERSION, FIRMWARE_6_2_SONG_VERSION};
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct MidiSettings {
@@ -24,23 +24,62 @@ impl TryFrom<&mut Reader> for MidiSettings {
 
     fn try_from(reader: &mut Reader) -> M8Result<Self> {
         Ok(Self {
-            receive_sync: reader.read_bool(),
-            receive_transport: reader.read(),
-            send_sync: reader.read_bool(),
-            send_transport: reader.read(),
-            record_note_channel: reader.read(),
-            record_note_velocity: reader.read_bool(),
-            record_note_delay_kill_commands: reader.read(),
-            control_map_channel: reader.read(),
-            song_row_cue_channel: reader.read(),
-            track_input_channel: reader.read_bytes(8).try_into().unwrap(),
-            track_input_intrument: reader.read_bytes(8).try_into().unwrap(),
-            track_input_program_change: reader.read_bool(),
-            track_input_mode: reader.read(),
+            receive_sync: reader.read_bool()?,
+            receive_transport: reader.read()?,
+            send_sync: reader.read_bool()?,
+            send_transport: reader.read()?,
+            record_note_channel: reader.read()?,
+            record_note_velocity: reader.read_bool()?,
+            record_note_delay_kill_commands: reader.read()?,
+            control_map_channel: reader.read()?,
+            song_row_cue_channel: reader.read()?,
+            track_input_channel: reader.read_bytes(8)?.try_into().unwrap(),
+            track_input_intrument: reader.read_bytes(8)?.try_into().unwrap(),
+            track_input_program_change: reader.read_bool()?,
+            track_input_mode: reader.read()?,
         })
     }
 }
 
+impl Default for MidiSettings {
+    /// Settings of a freshly created song
+    fn default() -> Self {
+        Self {
+            receive_sync: false,
+            receive_transport: 0,
+            send_sync: false,
+            send_transport: 0,
+            record_note_channel: 9,
+            record_note_velocity: true,
+            record_note_delay_kill_commands: 0,
+            control_map_channel: 17,
+            song_row_cue_channel: 11,
+            track_input_channel: [1, 2, 3, 4, 5, 6, 7, 8],
+            track_input_intrument: [0; 8],
+            track_input_program_change: true,
+            track_input_mode: 1,
+        }
+    }
+}
+
+impl MidiSettings {
+    pub fn write(&self, w: &mut Writer) {
+        w.write_bool(self.receive_sync);
+        w.write(self.receive_transport);
+        w.write_bool(self.send_sync);
+        w.write(self.send_transport);
+        w.write(self.record_note_channel);
+        w.write_bool(self.record_note_velocity);
+        w.write(self.record_note_delay_kill_commands);
+        w.write(self.control_map_channel);
+        w.write(self.song_row_cue_channel);
+        w.write_bytes(&self.track_input_channel);
+        w.write_bytes(&self.track_input_intrument);
+        w.write_bool(self.track_input_program_change);
+        w.write(self.track_input_mode);
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct LimiterParameter {
     pub level: u8,
@@ -64,23 +103,59 @@ pub struct MixerSettings {
 }
 
 impl MixerSettings {
+    /// Mixer of a freshly created song for a given version
+    pub fn default_ver(ver: Version) -> Self {
+        let silent_input = InputMixerSettings {
+            volume: 0,
+            mfx: 0,
+            delay: 0,
+            reverb: 0,
+        };
+
+        Self {
+            master_volume: 0xE0,
+            track_volume: [0xE0; 8],
+            chorus_volume: 0xE0,
+            delay_volume: 0xE0,
+            reverb_volume: 0xE0,
+            analog_input: AnalogInputSettings::Stereo(silent_input.clone()),
+            usb_input: silent_input,
+            dj_filter: 0x80,
+            dj_peak: 0,
+            dj_filter_type: 0,
+            limiter: LimiterParameter {
+                level: 0,
+                attack_release: if ver.after(&FIRMWARE_6_0_SONG_VERSION) {
+                    Some((0, 0x10, false))
+                } else {
+                    None
+                },
+            },
+            ott_level: if ver.after(&FIRMWARE_6_2_SONG_VERSION) {
+                Some(0)
+            } else {
+                None
+            },
+        }
+    }
+
     pub(crate) fn from_reader(reader: &mut Reader, ver: Version) -> M8Result<Self> {
-        let master_volume = reader.read();
-        let master_limit = reader.read();
-        let track_volume: [u8; 8] = reader.read_bytes(8).try_into().unwrap();
-        let chorus_volume = reader.read();
-        let delay_volume = reader.read();
-        let reverb_volume = reader.read();
-        let analog_input_volume = (reader.read(), reader.read());
-        let usb_input_volume = reader.read();
+        let master_volume = reader.read()?;
+        let master_limit = reader.read()?;
+        let track_volume: [u8; 8] = reader.read_bytes(8)?.try_into().unwrap();
+        let chorus_volume = reader.read()?;
+        let delay_volume = reader.read()?;
+        let reverb_volume = reader.read()?;
+        let analog_input_volume = (reader.read()?, reader.read()?);
+        let usb_input_volume = reader.read()?;
 
         let analog_input_l =
-            InputMixerSettings::from_reader(reader, analog_input_volume.0);
+            InputMixerSettings::from_reader(reader, analog_input_volume.0)?;
         let analog_input_r =
-            InputMixerSettings::from_reader(reader, analog_input_volume.0);
-        let usb_input_chorus = reader.read();
-        let usb_input_delay = reader.read();
-        let usb_input_reverb = reader.read();
+            InputMixerSettings::from_reader(reader, analog_input_volume.1)?;
+        let usb_input_chorus = reader.read()?;
+        let usb_input_delay = reader.read()?;
+        let usb_input_reverb = reader.read()?;
 
         let analog_input = if analog_input_volume.1 == 255 {
             AnalogInputSettings::Stereo(analog_input_l)
@@ -94,21 +169,21 @@ impl MixerSettings {
             reverb: usb_input_reverb,
         };
 
-        let dj_filter = reader.read();
-        let dj_peak = reader.read();
-        let dj_filter_type = reader.read();
+        let dj_filter = reader.read()?;
+        let dj_peak = reader.read()?;
+        let dj_filter_type = reader.read()?;
 
         let limiter_conf = if !ver.after(&FIRMWARE_6_0_SONG_VERSION) {
             None
         } else {
-            let limiter_attack = reader.read();
-            let limiter_release = reader.read();
-            let soft_clip = reader.read();
+            let limiter_attack = reader.read()?;
+            let limiter_release = reader.read()?;
+            let soft_clip = reader.read()?;
             Some((limiter_attack, limiter_release, soft_clip != 0))
         };
 
         let ott_level = if ver.after(&FIRMWARE_6_2_SONG_VERSION) {
-            Some(reader.read())
+            Some(reader.read()?)
         } else {
             None
         };
@@ -131,6 +206,57 @@ impl MixerSettings {
             ott_level
         })
     }
+
+    pub fn write(&self, w: &mut Writer, ver: Version) {
+        w.write(self.master_volume);
+        w.write(self.limiter.level);
+        w.write_bytes(&self.track_volume);
+        w.write(self.chorus_volume);
+        w.write(self.delay_volume);
+        w.write(self.reverb_volume);
+
+        let (analog_l, analog_r) = match &self.analog_input {
+            AnalogInputSettings::Stereo(l) => (l, None),
+            AnalogInputSettings::DualMono((l, r)) => (l, Some(r)),
+        };
+
+        w.write(analog_l.volume);
+        w.write(analog_r.map_or(255, |r| r.volume));
+        w.write(self.usb_input.volume);
+
+        analog_l.write(w);
+        match analog_r {
+            // right channel is not tracked in stereo, leave it as is
+            None => w.skip(3),
+            Some(r) => r.write(w),
+        }
+
+        w.write(self.usb_input.mfx);
+        w.write(self.usb_input.delay);
+        w.write(self.usb_input.reverb);
+
+        w.write(self.dj_filter);
+        w.write(self.dj_peak);
+        w.write(self.dj_filter_type);
+
+        if ver.after(&FIRMWARE_6_0_SONG_VERSION) {
+            match self.limiter.attack_release {
+                None => w.skip(3),
+                Some((attack, release, soft_clip)) => {
+                    w.write(attack);
+                    w.write(release);
+                    w.write_bool(soft_clip);
+                }
+            }
+        }
+
+        if ver.after(&FIRMWARE_6_2_SONG_VERSION) {
+            match self.ott_level {
+                None => w.skip(1),
+                Some(ott) => w.write(ott),
+            }
+        }
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -142,14 +268,21 @@ pub struct InputMixerSettings {
 }
 
 impl InputMixerSettings {
-    pub fn from_reader(reader: &mut Reader, volume: u8) -> Self {
-        let chorus = reader.read();
-        let delay = reader.read();
-        let reverb = reader.read();
+    pub fn from_reader(reader: &mut Reader, volume: u8) -> M8Result<Self> {
+        let chorus = reader.read()?;
+        let delay = reader.read()?;
+        let reverb = reader.read()?;
 
-        Self {
+        Ok(Self {
             volume, mfx: chorus, delay, reverb
-        }
+        })
+    }
+
+    /// Write the send levels, volume is written by the mixer
+    pub fn write(&self, w: &mut Writer) {
+        w.write(self.mfx);
+        w.write(self.delay);
+        w.write(self.reverb);
     }
 }
 
@@ -169,10 +302,15 @@ pub struct EffectFilter {
 
 impl EffectFilter {
     fn from_reader(reader: &mut Reader) -> M8Result<Self> {
-        let high_pass = reader.read();
-        let low_pass = reader.read();
+        let high_pass = reader.read()?;
+        let low_pass = reader.read()?;
         Ok(EffectFilter { high_pass, low_pass })
     }
+
+    fn write(&self, w: &mut Writer) {
+        w.write(self.high_pass);
+        w.write(self.low_pass);
+    }
 }
 
 #[repr(u8)]
@@ -195,10 +333,15 @@ pub struct OttConfiguration {
 impl OttConfiguration {
     pub(crate) fn from_reader(reader: &mut Reader, _version: Version) -> M8Result<Self> {
         Ok(Self {
-            time: reader.read(),
-            color: reader.read()
+            time: reader.read()?,
+            color: reader.read()?
         })
     }
+
+    pub fn write(&self, w: &mut Writer) {
+        w.write(self.time);
+        w.write(self.color);
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -227,12 +370,59 @@ pub struct EffectsSettings {
 }
 
 impl EffectsSettings {
+    /// Effects of a freshly created song for a given version
+    pub fn default_ver(version: Version) -> Self {
+        let (delay_filter, reverb_filter) = if version.after(&FIRMWARE_4_0_SONG_VERSION) {
+            (None, None)
+        } else {
+            (
+                Some(EffectFilter { high_pass: 0x40, low_pass: 0xFF }),
+                Some(EffectFilter { high_pass: 0x10, low_pass: 0xE0 }),
+            )
+        };
+
+        let (reverb_shimmer, ott_configuration, mfx_kind) =
+            if version.after(&FIRMWARE_6_2_SONG_VERSION) {
+                (
+                    Some(0),
+                    Some(OttConfiguration { time: 0x80, color: 0x80 }),
+                    Some(FxKind::Chorus),
+                )
+            } else {
+                (None, None, None)
+            };
+
+        Self {
+            mfx_kind,
+            chorus_mod_depth: 0x40,
+            chorus_mod_freq: 0x80,
+            chorus_width: 0xFF,
+            chorus_reverb_send: 0,
+
+            delay_filter,
+            delay_time_l: 0x30,
+            delay_time_r: 0x30,
+            delay_feedback: 0x80,
+            delay_width: 0xFF,
+            delay_reverb_send: 0,
+
+            reverb_filter,
+            reverb_size: 0xFF,
+            reverb_damping: 0xC0,
+            reverb_mod_depth: 0x10,
+            reverb_mod_freq: 0xFF,
+            reverb_width: 0xFF,
+            reverb_shimmer,
+            ott_configuration,
+        }
+    }
+
     pub(crate) fn from_reader(reader: &mut Reader, version: Version) -> M8Result<Self> {
-        let chorus_mod_depth = reader.read();
-        let chorus_mod_freq = reader.read();
-        let chorus_width = reader.read();
-        let chorus_reverb_send = reader.read();
-        reader.read_bytes(3); //unused
+        let chorus_mod_depth = reader.read()?;
+        let chorus_mod_freq = reader.read()?;
+        let chorus_width = reader.read()?;
+        let chorus_reverb_send = reader.read()?;
+        reader.read_bytes(3)?; //unused
 
         let delay_filter=  EffectFilter::from_reader(reader)?;
         let delay_filter = if version.after(&FIRMWARE_4_0_SONG_VERSION) {
@@ -241,12 +431,12 @@ impl EffectsSettings {
             Some(delay_filter)
         };
 
-        let delay_time_l = reader.read();
-        let delay_time_r = reader.read();
-        let delay_feedback = reader.read();
-        let delay_width = reader.read();
-        let delay_reverb_send = reader.read();
-        reader.read_bytes(1); //unused
+        let delay_time_l = reader.read()?;
+        let delay_time_r = reader.read()?;
+        let delay_feedback = reader.read()?;
+        let delay_width = reader.read()?;
+        let delay_reverb_send = reader.read()?;
+        reader.read_bytes(1)?; //unused
 
         let reverb_filter= EffectFilter::from_reader(reader)?;
         let reverb_filter = if version.after(&FIRMWARE_4_0_SONG_VERSION) {
@@ -255,18 +445,16 @@ impl EffectsSettings {
             Some(reverb_filter)
         };
 
-        let reverb_size = reader.read();
-        let reverb_damping = reader.read();
-        let reverb_mod_depth = reader.read();
-        let reverb_mod_freq = reader.read();
-        let reverb_width = reader.read();
+        let reverb_size = reader.read()?;
+        let reverb_damping = reader.read()?;
+        let reverb_mod_depth = reader.read()?;
+        let reverb_mod_freq = reader.read()?;
+        let reverb_width = reader.read()?;
         let (reverb_shimmer, ott_configuration, mfx_kind) =
             if version.after(&FIRMWARE_6_2_SONG_VERSION) {
-                let shimmer = Some(reader.read());
+                let shimmer = Some(reader.read()?);
                 let ott = OttConfiguration::from_reader(reader, version)?;
-                let mfx = reader.read();
-                let kind =
-                   mfx.try_into().map_err(| _| ParseError(format!("Unknown MFX kind {}", mfx)))?;
+                let kind = reader.read_value("MFX kind")?;
                 (shimmer, Some(ott), Some(kind))
 
             } else {
@@ -297,9 +485,57 @@ impl EffectsSettings {
             ott_configuration
         })
     }
+
+    pub fn write(&self, w: &mut Writer, version: Version) {
+        w.write(self.chorus_mod_depth);
+        w.write(self.chorus_mod_freq);
+        w.write(self.chorus_width);
+        w.write(self.chorus_reverb_send);
+        w.skip(3); //unused
+
+        match &self.delay_filter {
+            None => w.skip(2),
+            Some(filter) => filter.write(w),
+        }
+
+        w.write(self.delay_time_l);
+        w.write(self.delay_time_r);
+        w.write(self.delay_feedback);
+        w.write(self.delay_width);
+        w.write(self.delay_reverb_send);
+        w.skip(1); //unused
+
+        match &self.reverb_filter {
+            None => w.skip(2),
+            Some(filter) => filter.write(w),
+        }
+
+        w.write(self.reverb_size);
+        w.write(self.reverb_damping);
+        w.write(self.reverb_mod_depth);
+        w.write(self.reverb_mod_freq);
+        w.write(self.reverb_width);
+
+        if version.after(&FIRMWARE_6_2_SONG_VERSION) {
+            match self.reverb_shimmer {
+                None => w.skip(1),
+                Some(shimmer) => w.write(shimmer),
+            }
+
+            match &self.ott_configuration {
+                None => w.skip(2),
+                Some(ott) => ott.write(w),
+            }
+
+            match self.mfx_kind {
+                None => w.skip(1),
+                Some(kind) => w.write(kind.into()),
+            }
+        }
+    }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, Default)]
 pub struct MidiMapping {
     pub channel: u8,
     pub control_number: u8,
@@ -313,16 +549,26 @@ pub struct MidiMapping {
 impl MidiMapping {
     pub(crate) fn from_reader(reader: &mut Reader) -> M8Result<Self> {
         Ok(Self {
-            channel: reader.read(),
-            control_number: reader.read(),
-            value: reader.read(),
-            typ: reader.read(),
-            param_index: reader.read(),
-            min_value: reader.read(),
-            max_value: reader.read(),
+            channel: reader.read()?,
+            control_number: reader.read()?,
+            value: reader.read()?,
+            typ: reader.read()?,
+            param_index: reader.read()?,
+            min_value: reader.read()?,
+            max_value: reader.read()?,
         })
     }
 
+    pub fn write(&self, w: &mut Writer) {
+        w.write(self.channel);
+        w.write(self.control_number);
+        w.write(self.value);
+        w.write(self.typ);
+        w.write(self.param_index);
+        w.write(self.min_value);
+        w.write(self.max_value);
+    }
+
     pub fn empty(&self) -> bool {
         self.channel == 0
     }
@@ -0,0 +1,978 @@
+//! Standard MIDI file (SMF) export of a song arrangement, and
+//! import of MIDI notes into phrases and chains.
+use crate::fx::*;
+use crate::instruments::CommandPack;
+use crate::reader::*;
+use crate::remapper::{find_referenced_chains, find_referenced_phrases, try_allocate};
+use crate::songs::*;
+use crate::timeline::*;
+
+/// MIDI ticks per quarter note in exported files, chosen to be a
+/// multiple of every groove PPQN.
+pub const SMF_DIVISION: u16 = 192;
+
+/// Velocity used for notes before any velocity is set
+const DEFAULT_VELOCITY: u8 = 0x7F;
+
+/// Event of an exported MIDI track
+#[derive(PartialEq, Debug, Clone)]
+pub enum SmfEvent {
+    NoteOn { channel: u8, key: u8, velocity: u8 },
+    NoteOff { channel: u8, key: u8 },
+    ProgramChange { channel: u8, program: u8 },
+
+    /// Microseconds per quarter note
+    Tempo(u32),
+    Text(String),
+    TrackName(String),
+}
+
+impl SmfEvent {
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            SmfEvent::NoteOn { channel, key, velocity } => {
+                out.extend_from_slice(&[0x90 | channel, *key, *velocity])
+            }
+            SmfEvent::NoteOff { channel, key } => out.extend_from_slice(&[0x80 | channel, *key, 0]),
+            SmfEvent::ProgramChange { channel, program } => {
+                out.extend_from_slice(&[0xC0 | channel, *program])
+            }
+            SmfEvent::Tempo(us) => {
+                out.extend_from_slice(&[0xFF, 0x51, 3]);
+                out.extend_from_slice(&us.to_be_bytes()[1..]);
+            }
+            SmfEvent::Text(txt) => write_meta(out, 0x01, txt.as_bytes()),
+            SmfEvent::TrackName(txt) => write_meta(out, 0x03, txt.as_bytes()),
+        }
+    }
+}
+
+/// Event with its absolute time, in MIDI ticks
+#[derive(PartialEq, Debug, Clone)]
+pub struct SmfTimedEvent {
+    pub tick: u64,
+    pub event: SmfEvent,
+}
+
+fn write_meta(out: &mut Vec<u8>, kind: u8, data: &[u8]) {
+    out.push(0xFF);
+    out.push(kind);
+    write_var_len(out, data.len() as u32);
+    out.extend_from_slice(data);
+}
+
+pub(crate) fn write_var_len(out: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        bytes.push(0x80 | (rest & 0x7F) as u8);
+        rest >>= 7;
+    }
+
+    out.extend(bytes.iter().rev());
+}
+
+fn write_track_chunk(out: &mut Vec<u8>, events: &[SmfTimedEvent]) {
+    let mut data = vec![];
+    let mut last_tick = 0;
+
+    for timed in events {
+        write_var_len(&mut data, (timed.tick - last_tick) as u32);
+        timed.event.write(&mut data);
+        last_tick = timed.tick;
+    }
+
+    // end of track
+    data.extend_from_slice(&[0, 0xFF, 0x2F, 0]);
+
+    out.extend_from_slice(b"MTrk");
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(&data);
+}
+
+fn tempo_event(bpm: f32) -> SmfEvent {
+    SmfEvent::Tempo((60_000_000.0 / bpm.max(1.0)) as u32)
+}
+
+/// Commands already played out by the [`Timeline`], not exported as text
+const TIMELINE_COMMANDS: [&str; 7] = ["HOP", "KIL", "DEL", "REP", "RET", "GRV", "TPO"];
+
+/// Export state of a track
+struct TrackExport<'a> {
+    song: &'a Song,
+    channel: u8,
+    tick: u64,
+    instrument: usize,
+    velocity: u8,
+    playing: Option<u8>,
+    events: Vec<SmfTimedEvent>,
+    tempo_events: Vec<SmfTimedEvent>,
+}
+
+impl<'a> TrackExport<'a> {
+    fn push(&mut self, event: SmfEvent) {
+        self.events.push(SmfTimedEvent { tick: self.tick, event });
+    }
+
+    fn stop_note(&mut self) {
+        if let Some(key) = self.playing.take() {
+            self.push(SmfEvent::NoteOff { channel: self.channel, key });
+        }
+    }
+
+    fn command_pack(&self) -> CommandPack {
+        match self.song.instruments.get(self.instrument) {
+            Some(instr) => instr.instr_command_text(self.song.version),
+            None => CommandPack::default(),
+        }
+    }
+
+    fn play_event(&mut self, event: &TimelineEvent) {
+        self.tick = smf_tick(event.position);
+
+        if (event.instrument as usize) < Song::N_INSTRUMENTS
+            && event.instrument as usize != self.instrument
+        {
+            self.instrument = event.instrument as usize;
+            self.push(SmfEvent::ProgramChange {
+                channel: self.channel,
+                program: event.instrument,
+            });
+        }
+
+        if event.velocity != 0xFF {
+            self.velocity = event.velocity.min(0x7F);
+        }
+
+        if event.note.0 >= 0x80 && !event.note.is_empty() {
+            self.stop_note();
+        } else if !event.note.is_empty() {
+            self.stop_note();
+            self.push(SmfEvent::NoteOn {
+                channel: self.channel,
+                key: event.note.0,
+                velocity: self.velocity,
+            });
+            self.playing = Some(event.note.0);
+        }
+
+        let fx_commands = FX::fx_command_names(self.song.version);
+        let pack = self.command_pack();
+        for fx in event.fx {
+            if fx.is_empty() {
+                continue;
+            }
+
+            match fx_commands.try_render(fx.command) {
+                Some("TPO") if fx.value > 0 => {
+                    let event = tempo_event(f32::from(fx.value));
+                    self.tempo_events.push(SmfTimedEvent { tick: self.tick, event });
+                }
+                Some(cmd) if TIMELINE_COMMANDS.contains(&cmd) => {}
+                _ => {
+                    let text = fx.print(fx_commands, pack, &ReferenceTemplating::default());
+                    self.push(SmfEvent::Text(text));
+                }
+            }
+        }
+    }
+}
+
+/// MIDI tick of a timeline position in exported files
+fn smf_tick(position: TimelinePosition) -> u64 {
+    (position.beats * f64::from(SMF_DIVISION)).round() as u64
+}
+
+impl Song {
+    /// MIDI ticks of every phrase row for a given division,
+    /// from the first groove.
+    fn smf_step_ticks(&self, division: u16) -> Vec<f64> {
+        let groove = &self.grooves[0];
+        let tick_length = f64::from(division) / groove.active_ppqn() as f64;
+        let steps = groove.active_steps();
+
+        if steps.is_empty() {
+            vec![6.0 * tick_length]
+        } else {
+            steps.iter().map(|&s| f64::from(s) * tick_length).collect()
+        }
+    }
+
+    /// Compute the MIDI events of a song track (0 to 7) from the song
+    /// timeline, and the tempo changes found on the way.
+    pub fn smf_track_events(
+        &self,
+        timeline: &Timeline,
+        track: usize,
+    ) -> (Vec<SmfTimedEvent>, Vec<SmfTimedEvent>) {
+        let mut export = TrackExport {
+            song: self,
+            channel: track as u8,
+            tick: 0,
+            instrument: Song::N_INSTRUMENTS,
+            velocity: DEFAULT_VELOCITY,
+            playing: None,
+            events: vec![SmfTimedEvent {
+                tick: 0,
+                event: SmfEvent::TrackName(format!("Track {}", track + 1)),
+            }],
+            tempo_events: vec![],
+        };
+
+        for event in timeline.events.iter().filter(|e| e.track == track) {
+            export.play_event(event);
+        }
+
+        // rounding may put the last event after the end of the track
+        export.tick = export.tick.max(smf_tick(timeline.track_ends[track]));
+        export.stop_note();
+        (export.events, export.tempo_events)
+    }
+
+    /// Render the song arrangement as a type 1 standard MIDI file,
+    /// with a tempo track followed by one track per song track.
+    ///
+    /// Notes are exported with the M8 note number, on the channel
+    /// of their track. Instrument changes are program changes and
+    /// effects not played by the [`Timeline`] are text events.
+    pub fn to_midi_file(&self) -> Vec<u8> {
+        let timeline = self.timeline();
+        let mut tempo_track = vec![
+            SmfTimedEvent { tick: 0, event: SmfEvent::TrackName(self.name.clone()) },
+            SmfTimedEvent { tick: 0, event: tempo_event(self.tempo) },
+        ];
+
+        let mut tracks = vec![];
+        for track in 0..SongSteps::TRACK_COUNT {
+            let (events, tempos) = self.smf_track_events(&timeline, track);
+            tempo_track.extend(tempos);
+            tracks.push(events);
+        }
+        tempo_track.sort_by_key(|e| e.tick);
+
+        let mut out = vec![];
+        out.extend_from_slice(b"MThd");
+        out.extend_from_slice(&6u32.to_be_bytes());
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&(tracks.len() as u16 + 1).to_be_bytes());
+        out.extend_from_slice(&SMF_DIVISION.to_be_bytes());
+
+        write_track_chunk(&mut out, &tempo_track);
+        for events in &tracks {
+            write_track_chunk(&mut out, events);
+        }
+
+        out
+    }
+}
+
+/// Content of a standard MIDI file, only keeping the events
+/// described by [`SmfEvent`].
+#[derive(PartialEq, Debug, Clone)]
+pub struct SmfFile {
+    /// Ticks per quarter note
+    pub division: u16,
+    pub tracks: Vec<Vec<SmfTimedEvent>>,
+}
+
+/// Bounds checked cursor over the MIDI file bytes
+struct SmfCursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// End of the chunk being read
+    end: usize,
+    section: Section,
+}
+
+impl<'a> SmfCursor<'a> {
+    fn bytes(&mut self, count: usize) -> M8Result<&'a [u8]> {
+        if self.pos + count > self.end {
+            return Err(ParseError::Truncated {
+                section: self.section,
+                offset: self.end,
+                needed: self.pos + count - self.end,
+            });
+        }
+
+        let bytes = &self.data[self.pos..self.pos + count];
+        self.pos += count;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> M8Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> M8Result<u16> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> M8Result<u32> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn invalid(&self, offset: usize, field: &'static str, value: u32) -> ParseError {
+        ParseError::InvalidValue { section: self.section, offset, field, value }
+    }
+
+    fn var_len(&mut self) -> M8Result<u32> {
+        let start = self.pos;
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let b = self.byte()?;
+            value = (value << 7) | u32::from(b & 0x7F);
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(self.invalid(start, "variable length quantity", value))
+    }
+}
+
+impl SmfFile {
+    pub fn parse(data: &[u8]) -> M8Result<SmfFile> {
+        let mut cursor = SmfCursor { data, pos: 0, end: data.len(), section: Section::MidiChunk(0) };
+        let magic = cursor.bytes(4)?;
+        if magic != b"MThd" {
+            let value = u32::from_be_bytes(magic.try_into().unwrap());
+            return Err(cursor.invalid(0, "MIDI file header", value));
+        }
+
+        let header_len = cursor.u32()? as usize;
+        let header_end = cursor.pos + header_len;
+        let _format = cursor.u16()?;
+        let track_count = cursor.u16()?;
+        let division = cursor.u16()?;
+        if division & 0x8000 != 0 {
+            return Err(cursor.invalid(cursor.pos - 2, "time division", u32::from(division)));
+        }
+        cursor.pos = header_end;
+
+        let mut tracks = vec![];
+        let mut chunk_index = 0;
+        while tracks.len() < track_count as usize && cursor.pos < data.len() {
+            chunk_index += 1;
+            cursor.section = Section::MidiChunk(chunk_index);
+            let kind = cursor.bytes(4)?;
+            let len = cursor.u32()? as usize;
+            let start = cursor.pos;
+            cursor.bytes(len)?;
+
+            // unknown chunks are to be ignored
+            if kind == b"MTrk" {
+                let mut track = SmfCursor { data, pos: start, end: start + len, section: cursor.section };
+                tracks.push(Self::parse_track(&mut track)?);
+            }
+        }
+
+        Ok(SmfFile { division, tracks })
+    }
+
+    fn parse_track(cursor: &mut SmfCursor) -> M8Result<Vec<SmfTimedEvent>> {
+        let mut events = vec![];
+        let mut tick = 0u64;
+        let mut status = 0u8;
+
+        while cursor.pos < cursor.end {
+            tick += u64::from(cursor.var_len()?);
+
+            let first = cursor.byte()?;
+            let data_byte = if first & 0x80 != 0 {
+                status = first;
+                None
+            } else if status == 0 {
+                return Err(cursor.invalid(cursor.pos - 1, "running status data", u32::from(first)));
+            } else {
+                Some(first)
+            };
+
+            let channel = status & 0x0F;
+            let event = match status {
+                0xFF => {
+                    status = 0;
+                    let kind = cursor.byte()?;
+                    let len = cursor.var_len()? as usize;
+                    let payload = cursor.bytes(len)?;
+                    match kind {
+                        0x2F => break,
+                        0x01 => Some(SmfEvent::Text(String::from_utf8_lossy(payload).to_string())),
+                        0x03 => Some(SmfEvent::TrackName(String::from_utf8_lossy(payload).to_string())),
+                        0x51 if len == 3 => Some(SmfEvent::Tempo(
+                            u32::from_be_bytes([0, payload[0], payload[1], payload[2]]),
+                        )),
+                        _ => None,
+                    }
+                }
+                0xF0 | 0xF7 => {
+                    status = 0;
+                    let len = cursor.var_len()? as usize;
+                    cursor.bytes(len)?;
+                    None
+                }
+                _ => {
+                    let first = match data_byte {
+                        Some(b) => b,
+                        None => cursor.byte()?,
+                    };
+
+                    match status & 0xF0 {
+                        0x80 => {
+                            cursor.byte()?;
+                            Some(SmfEvent::NoteOff { channel, key: first })
+                        }
+                        0x90 => match cursor.byte()? {
+                            0 => Some(SmfEvent::NoteOff { channel, key: first }),
+                            velocity => Some(SmfEvent::NoteOn { channel, key: first, velocity }),
+                        },
+                        0xC0 => Some(SmfEvent::ProgramChange { channel, program: first }),
+                        0xD0 => None,
+                        _ => {
+                            cursor.byte()?;
+                            None
+                        }
+                    }
+                }
+            };
+
+            if let Some(event) = event {
+                events.push(SmfTimedEvent { tick, event });
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+/// Where to put the notes of an imported MIDI file
+#[derive(PartialEq, Debug, Clone)]
+pub struct SmfImportOptions {
+    /// First song track receiving notes, each MIDI track and channel
+    /// with notes goes on the next song track.
+    pub first_track: usize,
+
+    /// Number of song tracks that can be filled
+    pub track_count: usize,
+
+    /// Song row of the first imported chain
+    pub start_row: usize,
+
+    /// Instrument set on the first note of every phrase
+    pub instrument: u8,
+}
+
+/// Result of a MIDI file import
+#[derive(PartialEq, Debug, Clone)]
+pub struct SmfImportReport {
+    /// Song track and chains placed on it, from the start row.
+    /// Rows without any note are left empty (FF).
+    pub chains: Vec<(usize, Vec<u8>)>,
+
+    /// Notes starting on a step already holding a note, M8
+    /// tracks being monophonic.
+    pub dropped_notes: usize,
+
+    /// MIDI tracks or channels with notes that did not fit in
+    /// the track range.
+    pub ignored_lanes: usize,
+}
+
+/// Notes of a MIDI track on a single channel, as (start, end, key, velocity)
+type SmfLane = Vec<(u64, u64, u8, u8)>;
+
+fn collect_lanes(smf: &SmfFile) -> Vec<SmfLane> {
+    let mut lanes = vec![];
+
+    for track in &smf.tracks {
+        let mut channels: Vec<SmfLane> = vec![vec![]; 16];
+        let mut playing: Vec<Option<(u64, u8)>> = vec![None; 16 * 128];
+
+        for timed in track {
+            match timed.event {
+                SmfEvent::NoteOn { channel, key, velocity } => {
+                    let ix = channel as usize * 128 + key as usize;
+                    if let Some((start, vel)) = playing[ix] {
+                        channels[channel as usize].push((start, timed.tick, key, vel));
+                    }
+                    playing[ix] = Some((timed.tick, velocity));
+                }
+                SmfEvent::NoteOff { channel, key } => {
+                    let ix = channel as usize * 128 + key as usize;
+                    if let Some((start, vel)) = playing[ix].take() {
+                        channels[channel as usize].push((start, timed.tick, key, vel));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for mut notes in channels {
+            if !notes.is_empty() {
+                notes.sort_by_key(|n| n.0);
+                lanes.push(notes);
+            }
+        }
+    }
+
+    lanes
+}
+
+/// Most steps an import can fill, every phrase step of every
+/// chain step of every song row.
+const SMF_MAX_STEPS: usize = SongSteps::ROW_COUNT * 16 * 16;
+
+impl Song {
+    /// Start of every step in MIDI ticks, until `end` is covered
+    fn smf_step_starts(&self, division: u16, end: u64) -> Result<Vec<f64>, String> {
+        if division == 0 {
+            return Err("MIDI file has a time division of 0".to_string());
+        }
+
+        let step_ticks = self.smf_step_ticks(division);
+        if step_ticks.contains(&0.0) {
+            return Err("The first groove has steps of 0 ticks".to_string());
+        }
+
+        let cycle: f64 = step_ticks.iter().sum();
+        let needed = (end as f64 / cycle).ceil() * step_ticks.len() as f64;
+        if needed > SMF_MAX_STEPS as f64 {
+            return Err(format!("MIDI file is longer than the {} steps of a song", SMF_MAX_STEPS));
+        }
+
+        let mut starts = vec![];
+        let mut tick = 0.0;
+
+        while starts.is_empty() || tick < end as f64 || starts.len() % 16 != 0 {
+            let row = starts.len() % 16;
+            starts.push(tick);
+            tick += step_ticks[row % step_ticks.len()];
+        }
+
+        Ok(starts)
+    }
+
+    /// Quantize the notes of a lane on the song grid
+    fn smf_lane_steps(&self, lane: &SmfLane, starts: &[f64], instrument: u8, dropped: &mut usize) -> Vec<Step> {
+        let nearest = |tick: u64| -> usize {
+            let tick = tick as f64;
+            let after = starts.partition_point(|&s| s < tick).min(starts.len() - 1);
+            if after > 0 && tick - starts[after - 1] < starts[after] - tick {
+                after - 1
+            } else {
+                after
+            }
+        };
+
+        let mut steps = vec![Step::empty(); starts.len()];
+        let mut last_off: Option<usize> = None;
+
+        for &(start, end, key, velocity) in lane {
+            let step = nearest(start);
+            if steps[step].note.0 < 0x80 {
+                *dropped += 1;
+                continue;
+            }
+
+            // the previous note is cut by this one
+            if let Some(off) = last_off {
+                if off > step && steps[off].note.0 == 0x80 {
+                    steps[off].note = Note::default();
+                }
+            }
+
+            steps[step].note = Note(key.min(0x7F));
+            steps[step].velocity = velocity.min(0x7F);
+
+            let off = nearest(end).max(step + 1);
+            if off < steps.len() && steps[off].note.is_empty() {
+                steps[off].note = Note(0x80);
+            }
+            last_off = Some(off);
+        }
+
+        // the first note of every phrase sets the instrument
+        for phrase in steps.chunks_mut(16) {
+            if let Some(first) = phrase.iter_mut().find(|s| s.note.0 < 0x80) {
+                first.instrument = instrument;
+            }
+        }
+
+        steps
+    }
+
+    /// Import the notes of a standard MIDI file in new phrases and
+    /// chains, placed on the song from the start row.
+    ///
+    /// Notes are quantized on the steps of the first groove, phrases
+    /// and chains are allocated in free slots. Identical phrases and
+    /// chains already in use are reused, phrases and chains without
+    /// any note are left empty. Files longer than the song
+    /// can hold and grooves with 0 tick steps are rejected. The song
+    /// is left untouched on error.
+    pub fn import_midi_file(&mut self, smf: &SmfFile, options: &SmfImportOptions) -> Result<SmfImportReport, String> {
+        if options.first_track + options.track_count > SongSteps::TRACK_COUNT {
+            return Err(format!(
+                "Track range {}..{} is outside of the song",
+                options.first_track,
+                options.first_track + options.track_count
+            ));
+        }
+
+        let lanes = collect_lanes(smf);
+        let end = lanes.iter().flatten().map(|n| n.1).max().unwrap_or(0);
+        let starts = self.smf_step_starts(smf.division, end)?;
+
+        let mut song = self.clone();
+        let mut allocated_phrases = find_referenced_phrases(&song);
+        let mut allocated_chains = find_referenced_chains(&song);
+        let mut report = SmfImportReport {
+            chains: vec![],
+            dropped_notes: 0,
+            ignored_lanes: lanes.len().saturating_sub(options.track_count),
+        };
+
+        for (lane, track) in lanes.iter().take(options.track_count).zip(options.first_track..) {
+            let steps = self.smf_lane_steps(lane, &starts, options.instrument, &mut report.dropped_notes);
+
+            let mut phrase_ids = vec![];
+            for phrase_steps in steps.chunks(16) {
+                if phrase_steps.iter().all(|s| s.is_empty()) {
+                    phrase_ids.push(0xFF);
+                    continue;
+                }
+
+                let mut phrase = Phrase::empty(song.version);
+                phrase.steps.clone_from_slice(phrase_steps);
+
+                let known = (0..Song::N_PHRASES)
+                    .find(|&p| allocated_phrases[p] && song.phrases[p].steps == phrase.steps);
+                let slot = match known {
+                    Some(p) => p,
+                    None => try_allocate(&allocated_phrases, 0)
+                        .ok_or_else(|| "No more available phrase slots".to_string())?,
+                };
+
+                allocated_phrases[slot] = true;
+                song.phrases[slot] = phrase;
+                phrase_ids.push(slot as u8);
+            }
+
+            let mut chain_ids = vec![];
+            for chain_phrases in phrase_ids.chunks(16) {
+                if chain_phrases.iter().all(|&p| p == 0xFF) {
+                    chain_ids.push(0xFF);
+                    continue;
+                }
+
+                let mut chain = Chain::default();
+                for (chain_step, phrase) in chain.steps.iter_mut().zip(chain_phrases) {
+                    chain_step.phrase = *phrase;
+                }
+
+                let known = (0..Song::N_CHAINS)
+                    .find(|&c| allocated_chains[c] && song.chains[c].steps == chain.steps);
+                let slot = match known {
+                    Some(c) => c,
+                    None => try_allocate(&allocated_chains, 0)
+                        .ok_or_else(|| "No more available chain slots".to_string())?,
+                };
+
+                allocated_chains[slot] = true;
+                song.chains[slot] = chain;
+                chain_ids.push(slot as u8);
+            }
+
+            if options.start_row + chain_ids.len() > SongSteps::ROW_COUNT {
+                return Err(format!("Not enough song rows for the {} chains of track {}", chain_ids.len(), track));
+            }
+
+            for (i, chain) in chain_ids.iter().enumerate() {
+                song.song.steps[(options.start_row + i) * SongSteps::TRACK_COUNT + track] = *chain;
+            }
+
+            report.chains.push((track, chain_ids));
+        }
+
+        *self = song;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::smf::*;
+    use crate::Version;
+
+    fn simple_song() -> Song {
+        let mut song = Song::new(Version::new(6, 6));
+        song.name = "SMF".to_string();
+
+        let steps = &mut song.phrases[1].steps;
+        steps[0].note = Note(0x24);
+        steps[0].velocity = 0x40;
+        steps[0].instrument = 2;
+        steps[4].note = Note(0x80);
+        steps[8].note = Note(0x27);
+        steps[8].fx1 = FX { command: 0x00, value: 0x10 }; // ARP
+
+        song.chains[0].steps[0].phrase = 1;
+        song.chains[0].steps[1].phrase = 1;
+        song.chains[0].steps[1].transpose = 0xFE;
+        song.song.steps[0] = 0;
+        song
+    }
+
+    #[test]
+    fn test_track_events() {
+        let song = simple_song();
+        let timeline = song.timeline();
+        let (events, tempos) = song.smf_track_events(&timeline, 0);
+        let step = 6 * 192 / 24;
+
+        let at = |tick: u64, event: SmfEvent| SmfTimedEvent { tick, event };
+        assert_eq!(
+            events,
+            vec![
+                at(0, SmfEvent::TrackName("Track 1".to_string())),
+                at(0, SmfEvent::ProgramChange { channel: 0, program: 2 }),
+                at(0, SmfEvent::NoteOn { channel: 0, key: 0x24, velocity: 0x40 }),
+                at(4 * step, SmfEvent::NoteOff { channel: 0, key: 0x24 }),
+                at(8 * step, SmfEvent::NoteOn { channel: 0, key: 0x27, velocity: 0x40 }),
+                at(8 * step, SmfEvent::Text("ARP10".to_string())),
+                at(16 * step, SmfEvent::NoteOff { channel: 0, key: 0x27 }),
+                at(16 * step, SmfEvent::NoteOn { channel: 0, key: 0x22, velocity: 0x40 }),
+                at(20 * step, SmfEvent::NoteOff { channel: 0, key: 0x22 }),
+                at(24 * step, SmfEvent::NoteOn { channel: 0, key: 0x25, velocity: 0x40 }),
+                at(24 * step, SmfEvent::Text("ARP10".to_string())),
+                at(32 * step, SmfEvent::NoteOff { channel: 0, key: 0x25 }),
+            ]
+        );
+        assert!(tempos.is_empty());
+
+        let (events, _) = song.smf_track_events(&timeline, 1);
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn test_repeat_past_track_end() {
+        let mut song = simple_song();
+        song.phrases[1].steps[15].note = Note(0x30);
+        song.phrases[1].steps[15].fx1 = FX { command: 0x09, value: 0xFF }; // REP
+
+        let timeline = song.timeline();
+        let end = timeline.track_ends[0];
+        assert!(timeline.events.iter().all(|e| e.position.beats < end.beats));
+
+        let smf = SmfFile::parse(&song.to_midi_file()).expect("Could not parse MIDI file");
+        let last = smf.tracks[1].last().unwrap();
+        assert_eq!(last.tick, smf_tick(end));
+        assert_eq!(last.event, SmfEvent::NoteOff { channel: 0, key: 0x2E });
+    }
+
+    #[test]
+    fn test_midi_file_layout() {
+        let song = simple_song();
+        let smf = song.to_midi_file();
+
+        assert_eq!(&smf[0..4], b"MThd");
+        assert_eq!(&smf[8..14], &[0, 1, 0, 9, 0, 192]);
+
+        let mut pos = 14;
+        let mut chunks = 0;
+        while pos < smf.len() {
+            assert_eq!(&smf[pos..pos + 4], b"MTrk");
+            let len = u32::from_be_bytes(smf[pos + 4..pos + 8].try_into().unwrap()) as usize;
+            assert_eq!(&smf[pos + 8 + len - 3..pos + 8 + len], &[0xFF, 0x2F, 0]);
+            pos += 8 + len;
+            chunks += 1;
+        }
+        assert_eq!(chunks, 9);
+
+        // 120 BPM tempo in the first track
+        let tempo = [0xFF, 0x51, 3, 0x07, 0xA1, 0x20];
+        assert!(smf.windows(tempo.len()).any(|w| w == tempo));
+    }
+
+    #[test]
+    fn test_import_exported_file() {
+        let mut song = simple_song();
+        let smf = SmfFile::parse(&song.to_midi_file()).expect("Could not parse MIDI file");
+        assert_eq!(smf.division, SMF_DIVISION);
+        assert_eq!(smf.tracks.len(), 9);
+
+        let options = SmfImportOptions {
+            first_track: 3,
+            track_count: 2,
+            start_row: 4,
+            instrument: 5,
+        };
+        let report = song.import_midi_file(&smf, &options).expect("Could not import MIDI file");
+        assert_eq!(report.dropped_notes, 0);
+        assert_eq!(report.ignored_lanes, 0);
+        assert_eq!(report.chains.len(), 1);
+
+        let (track, chains) = &report.chains[0];
+        assert_eq!(*track, 3);
+        assert_eq!(chains.len(), 1);
+        assert_ne!(chains[0], 0);
+        assert_eq!(song.song.steps[4 * SongSteps::TRACK_COUNT + 3], chains[0]);
+
+        let chain = &song.chains[chains[0] as usize];
+        let first = &song.phrases[chain.steps[0].phrase as usize];
+        let second = &song.phrases[chain.steps[1].phrase as usize];
+        assert!(chain.steps[2].is_empty());
+        assert_ne!(chain.steps[0].phrase, 1);
+        assert_ne!(chain.steps[1].phrase, 1);
+
+        assert_eq!(first.steps[0].note, Note(0x24));
+        assert_eq!(first.steps[0].velocity, 0x40);
+        assert_eq!(first.steps[0].instrument, 5);
+        assert_eq!(first.steps[4].note, Note(0x80));
+        assert_eq!(first.steps[8].note, Note(0x27));
+        assert_eq!(first.steps[8].instrument, 0xFF);
+        assert!(first.steps[9..].iter().all(|s| s.is_empty()));
+
+        assert_eq!(second.steps[0].note, Note(0x22));
+        assert_eq!(second.steps[8].note, Note(0x25));
+
+        // source phrases are untouched
+        assert!(song.phrases[1] == simple_song().phrases[1]);
+    }
+
+    #[test]
+    fn test_import_polyphony() {
+        let at = |tick: u64, event: SmfEvent| SmfTimedEvent { tick, event };
+        let smf = SmfFile {
+            division: 96,
+            tracks: vec![vec![
+                at(0, SmfEvent::NoteOn { channel: 0, key: 40, velocity: 100 }),
+                at(0, SmfEvent::NoteOn { channel: 0, key: 43, velocity: 100 }),
+                at(0, SmfEvent::NoteOn { channel: 9, key: 36, velocity: 90 }),
+                at(26, SmfEvent::NoteOff { channel: 0, key: 40 }),
+                at(26, SmfEvent::NoteOff { channel: 0, key: 43 }),
+                at(95, SmfEvent::NoteOff { channel: 9, key: 36 }),
+            ]],
+        };
+
+        let mut song = Song::new(crate::Version::new(6, 6));
+        let options = SmfImportOptions {
+            first_track: 7,
+            track_count: 1,
+            start_row: 0,
+            instrument: 0,
+        };
+        let report = song.import_midi_file(&smf, &options).expect("Could not import MIDI file");
+        assert_eq!(report.dropped_notes, 1);
+        assert_eq!(report.ignored_lanes, 1);
+
+        let chain = &song.chains[report.chains[0].1[0] as usize];
+        let phrase = &song.phrases[chain.steps[0].phrase as usize];
+        assert_eq!(phrase.steps[0].note, Note(40));
+        assert_eq!(phrase.steps[1].note, Note(0x80));
+
+        let options = SmfImportOptions { first_track: 7, track_count: 2, ..options };
+        assert!(song.import_midi_file(&smf, &options).is_err());
+    }
+
+    #[test]
+    fn test_import_sparse_track() {
+        let at = |tick: u64, event: SmfEvent| SmfTimedEvent { tick, event };
+        let bar = 16 * 24;
+        let smf = SmfFile {
+            division: 96,
+            tracks: vec![vec![
+                at(0, SmfEvent::NoteOn { channel: 0, key: 40, velocity: 100 }),
+                at(24, SmfEvent::NoteOff { channel: 0, key: 40 }),
+                at(2 * bar, SmfEvent::NoteOn { channel: 0, key: 41, velocity: 100 }),
+                at(2 * bar + 24, SmfEvent::NoteOff { channel: 0, key: 41 }),
+                at(300 * bar, SmfEvent::NoteOn { channel: 0, key: 42, velocity: 100 }),
+                at(300 * bar + 24, SmfEvent::NoteOff { channel: 0, key: 42 }),
+            ]],
+        };
+        let options = SmfImportOptions {
+            first_track: 0,
+            track_count: 1,
+            start_row: 0,
+            instrument: 0,
+        };
+
+        let mut song = Song::new(crate::Version::new(6, 6));
+        let report = song.import_midi_file(&smf, &options).expect("Could not import MIDI file");
+        let chains = &report.chains[0].1;
+        assert_eq!(chains.len(), 19);
+        assert!(chains[1..18].iter().all(|&c| c == 0xFF));
+        assert_eq!(song.song.steps[SongSteps::TRACK_COUNT], 0xFF);
+
+        let chain = &song.chains[chains[0] as usize];
+        assert_ne!(chain.steps[0].phrase, 0xFF);
+        assert_eq!(chain.steps[1].phrase, 0xFF);
+        assert_ne!(chain.steps[2].phrase, 0xFF);
+        assert!(chain.steps[3..].iter().all(|s| s.phrase == 0xFF));
+
+        let used = song.phrases.iter().filter(|p| p.steps.iter().any(|s| !s.is_empty())).count();
+        assert_eq!(used, 3);
+    }
+
+    #[test]
+    fn test_import_rejected_grid() {
+        let at = |tick: u64, event: SmfEvent| SmfTimedEvent { tick, event };
+        let mut smf = SmfFile {
+            division: 96,
+            tracks: vec![vec![
+                at(0, SmfEvent::NoteOn { channel: 0, key: 40, velocity: 100 }),
+                at(24, SmfEvent::NoteOff { channel: 0, key: 40 }),
+            ]],
+        };
+        let options = SmfImportOptions {
+            first_track: 0,
+            track_count: 1,
+            start_row: 0,
+            instrument: 0,
+        };
+
+        let mut song = Song::new(crate::Version::new(6, 6));
+        song.grooves[0].steps[1] = 0;
+        assert!(song.import_midi_file(&smf, &options).is_err());
+
+        let mut song = Song::new(crate::Version::new(6, 6));
+        smf.tracks[0][1].tick = u64::from(u32::MAX) * 16;
+        assert!(song.import_midi_file(&smf, &options).is_err());
+        assert!(song == Song::new(crate::Version::new(6, 6)));
+    }
+
+    #[test]
+    fn test_running_status() {
+        let mut data = b"MThd\0\0\0\x06\0\0\0\x01\0\x60MTrk".to_vec();
+        let track = [0, 0x90, 60, 100, 10, 62, 100, 10, 60, 0, 0, 0xFF, 0x2F, 0];
+        data.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        data.extend_from_slice(&track);
+
+        let smf = SmfFile::parse(&data).expect("Could not parse MIDI file");
+        let at = |tick: u64, event: SmfEvent| SmfTimedEvent { tick, event };
+        assert_eq!(
+            smf.tracks,
+            vec![vec![
+                at(0, SmfEvent::NoteOn { channel: 0, key: 60, velocity: 100 }),
+                at(10, SmfEvent::NoteOn { channel: 0, key: 62, velocity: 100 }),
+                at(20, SmfEvent::NoteOff { channel: 0, key: 60 }),
+            ]]
+        );
+
+        let err = SmfFile::parse(&data[..data.len() - 3]).unwrap_err();
+        assert!(matches!(err, ParseError::Truncated { section: Section::MidiChunk(1), .. }));
+    }
+
+    #[test]
+    fn test_var_len() {
+        let encode = |v| {
+            let mut out = vec![];
+            write_var_len(&mut out, v);
+            out
+        };
+
+        assert_eq!(encode(0), vec![0]);
+        assert_eq!(encode(0x7F), vec![0x7F]);
+        assert_eq!(encode(0x80), vec![0x81, 0x00]);
+        assert_eq!(encode(0x0FFFFFFF), vec![0xFF, 0xFF, 0xFF, 0x7F]);
+    }
+}
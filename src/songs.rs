@@ -57,6 +57,16 @@ impl Offsets {
         // general EQ + 3 for effects + 1 global
         self.instrument_eq_count + 3 + 1
     }
+
+    /// Size of a song file using this layout for a given version
+    pub fn file_size(&self, version: Version) -> usize {
+        match (self.groove_ppqn_offsets, self.row_bookmark_offset) {
+            (_, Some(row_bookmarks)) if version.at_least(6, 6) =>
+                row_bookmarks + SongSteps::ROW_COUNT,
+            (Some(ppqn), _) if version.at_least(6, 5) => ppqn + Song::N_GROOVES,
+            _ => self.eq + self.eq_count() * Equ::V4_SIZE,
+        }
+    }
 }
 
 pub const V4_OFFSETS: Offsets = Offsets {
@@ -163,6 +173,115 @@ impl Song {
 
     pub const N_MIDI_MAPPINGS: usize = 128;
 
+    /// Default content of the EQs stored after the instrument ones
+    const NON_INSTRUMENT_EQS: [u8; 4 * Equ::V4_SIZE] = [
+        0x01, 0x64, 0x00, 0x00, 0x00, 0x32, 0x02, 0xE8, 0x03, 0x00, 0x00, 0x32, 0x04, 0x88, 0x13, 0x00, 0x00, 0x32,
+        0x00, 0x64, 0x00, 0x00, 0x00, 0x32, 0x02, 0xE8, 0x03, 0x00, 0x00, 0x32, 0x04, 0x88, 0x13, 0x00, 0x00, 0x32,
+        0x00, 0xF4, 0x01, 0x00, 0x00, 0x32, 0x02, 0xE8, 0x03, 0x00, 0x00, 0x32, 0x05, 0x10, 0x27, 0x00, 0x00, 0x32,
+        0x00, 0xC8, 0x00, 0x00, 0x00, 0x32, 0x02, 0xE8, 0x03, 0x00, 0x00, 0x32, 0x05, 0x60, 0x22, 0x00, 0x00, 0x32,
+    ];
+
+    /// Bytes of a new song from the 3 bytes preceding the effect
+    /// settings up to the MIDI mappings, as written by a firmware
+    /// prior to 6.2. The parsed effect settings are written over them
+    /// for the target version, the others are kept as the firmware
+    /// writes them.
+    const EFFECT_SETTINGS_AREA: [u8; 0x40] = [
+        0x19, 0x48, 0xA6, 0x40, 0x80, 0xFF, 0x00, 0xFD, 0xAF, 0x26, 0x40, 0xFF, 0x30, 0x30, 0x80, 0xFF,
+        0x00, 0x41, 0x10, 0xE0, 0xFF, 0xC0, 0x10, 0xFF, 0xFF, 0xF7, 0xDF, 0xD2, 0x9E, 0x43, 0x12, 0x49,
+        0x82, 0xD6, 0xF6, 0x79, 0x10, 0x80, 0x06, 0x33, 0xDF, 0x56, 0xE7, 0xE3, 0xB1, 0xBA, 0x04, 0xA6,
+        0xFF, 0xFD, 0x53, 0x7B, 0x81, 0x20, 0xAC, 0x8C, 0x31, 0x08, 0x29, 0x90, 0xF3, 0x03, 0x0F, 0xB7,
+    ];
+
+    /// Create an empty song, with the same content as a new song
+    /// made on a M8 running the given firmware version.
+    ///
+    /// Use [`Writer::new_song_writer`] to write it without any
+    /// existing song file.
+    pub fn new(version: Version) -> Song {
+        let eqs = if version.after(&FIRMWARE_4_0_SONG_VERSION) {
+            vec![Equ::empty(); Self::offsets_for(version).instrument_eq_count]
+        } else {
+            vec![]
+        };
+
+        Song {
+            version,
+            directory: "/Songs/".to_string(),
+            transpose: 0,
+            tempo: 120.0,
+            quantize: 0,
+            name: String::new(),
+            key: 0,
+
+            song: SongSteps::default_ver(version),
+            phrases: (0..Self::N_PHRASES).map(|_| Phrase::empty(version)).collect(),
+            chains: vec![Chain::default(); Self::N_CHAINS],
+            instruments: vec![Instrument::None; Self::N_INSTRUMENTS],
+            tables: (0..Self::N_TABLES).map(|_| Table::default_ver(version)).collect(),
+            grooves: (0..Self::N_GROOVES)
+                .map(|i| Groove::default_ver(i as u8, version))
+                .collect(),
+            scales: (0..Self::N_SCALES).map(|i| Scale::factory(i as u8)).collect(),
+
+            mixer_settings: MixerSettings::default_ver(version),
+            effects_settings: EffectsSettings::default_ver(version),
+            midi_settings: MidiSettings::default(),
+            midi_mappings: vec![MidiMapping::default(); Self::N_MIDI_MAPPINGS],
+            eqs,
+        }
+    }
+
+    /// Content of a song file with only empty slots, for the
+    /// layout of the given version.
+    pub(crate) fn blank_file(version: Version) -> Vec<u8> {
+        let ofs = Self::offsets_for(version);
+        let mut w = Writer::new(vec![0; ofs.file_size(version)]);
+
+        // Only the kind is written for an empty instrument, the rest of
+        // the slot is filled like the firmware does.
+        let mut empty_instrument = [0; Instrument::INSTRUMENT_MEMORY_SIZE];
+        empty_instrument[..0x57].fill(0xFF);
+        if version.after(&FIRMWARE_5_0_SONG_VERSION) {
+            empty_instrument[0x0D] = 0x01;
+            empty_instrument[0x3E] = 0x80;
+        } else {
+            empty_instrument[0x0D] = 0x41;
+        }
+        empty_instrument[0x0E] = 0x01;
+
+        w.seek(ofs.instruments);
+        for _ in 0..Self::N_INSTRUMENTS {
+            w.write_bytes(&empty_instrument);
+        }
+
+        w.seek(ofs.effect_settings - 3);
+        w.write_bytes(&Self::EFFECT_SETTINGS_AREA);
+        w.seek(ofs.effect_settings);
+        EffectsSettings::default_ver(version).write(&mut w, version);
+
+        // factory scale names are padded with 0xFF rather than zeros
+        let name_offset = Scale::V4_SIZE - 16;
+        for i in 0..Self::N_SCALES {
+            let name = Scale::factory(i as u8).name;
+            let mut field = [0xFF; 16];
+            field[..name.len()].copy_from_slice(name.as_bytes());
+            w.seek(ofs.scale + i * Scale::song_stride(version) + name_offset);
+            w.write_bytes(&field);
+        }
+
+        w.seek(ofs.eq);
+        for _ in 0..ofs.instrument_eq_count {
+            Equ::empty().write(&mut w);
+        }
+
+        // effects and global EQs are not parsed, so they are never
+        // written by the song.
+        w.write_bytes(&Self::NON_INSTRUMENT_EQS);
+
+        w.finish()
+    }
+
     pub fn phrase_view(&self, ix: usize) -> PhraseView<'_> {
         self.phrase_view_with_templates(ix, ReferenceTemplating::default())
     }
@@ -178,7 +297,12 @@ impl Song {
 
 
     pub fn offsets(&self) -> &'static Offsets {
-        if self.version.after(&FIRMWARE_5_0_SONG_VERSION) {
+        Self::offsets_for(self.version)
+    }
+
+    /// File layout used by a given version
+    pub fn offsets_for(version: Version) -> &'static Offsets {
+        if version.after(&FIRMWARE_5_0_SONG_VERSION) {
             &V4_1_OFFSETS
         } else {
             &V4_OFFSETS
@@ -211,40 +335,79 @@ impl Song {
     }
 
     pub fn read(reader: &mut impl std::io::Read) -> M8Result<Self> {
-        let mut buf: Vec<u8> = vec![];
-        reader.read_to_end(&mut buf).unwrap();
-        let mut reader = Reader::new(buf);
+        let mut reader = Reader::from_read(reader)?;
         Self::read_from_reader(&mut reader)
     }
 
     pub fn read_from_reader(mut reader: &mut Reader) -> M8Result<Self> {
-        if reader.len() < Self::SIZE_PRIOR_TO_2_5 + Version::SIZE {
-            return Err(ParseError(
-                "File is not long enough to be a M8 song".to_string(),
-            ));
-        }
+        reader.expect_len(Self::SIZE_PRIOR_TO_2_5 + Version::SIZE)?;
         let version = Version::from_reader(&mut reader)?;
-        if version.at_least(2, 5) && reader.len() < Self::SIZE + Version::SIZE {
-            return Err(ParseError(
-                "File is not long enough to be a M8 song".to_string(),
-            ));
+        if version.at_least(2, 5) {
+            reader.expect_len(Self::SIZE + Version::SIZE)?;
         }
 
-        Self::from_reader(&mut reader, version)
+        Self::from_reader(&mut reader, version, &mut Recovery::Strict)
+    }
+
+    /// Read a possibly damaged song, replacing every element that
+    /// can't be parsed (instrument, phrase, settings...) by its
+    /// default value. The replaced elements are listed in the
+    /// returned diagnostics. Only a file without a complete song
+    /// header is rejected.
+    pub fn read_lenient(reader: &mut impl std::io::Read) -> M8Result<(Self, Vec<Diagnostic>)> {
+        let mut reader = Reader::from_read(reader)?;
+        Self::read_from_reader_lenient(&mut reader)
+    }
+
+    /// Same as [`Song::read_lenient`] for an in-memory song
+    pub fn read_from_reader_lenient(reader: &mut Reader) -> M8Result<(Self, Vec<Diagnostic>)> {
+        reader.expect_len(V4_OFFSETS.groove)?;
+        let version = Version::from_reader(reader)?;
+        let mut recovery = Recovery::Lenient(vec![]);
+        let song = Self::from_reader(reader, version, &mut recovery)?;
+        Ok((song, recovery.diagnostics()))
     }
 
+    /// Write every parsed element of the song at its location in
+    /// the file.
     pub fn write(&self, w: &mut Writer) -> Result<(), String> {
         if !self.version.after(&FIRMWARE_4_0_SONG_VERSION) {
             Err(String::from(
                 "Only version 4.0 or above song can be rewritten",
             ))
         } else {
-            self.write_patterns(V4_OFFSETS, w);
+            let ofs = self.offsets();
+            self.write_header(w);
+            self.write_patterns(ofs, w);
+            self.write_settings(ofs, w);
             Ok(())
         }
     }
 
-    fn write_patterns(&self, ofs: Offsets, w: &mut Writer) {
+    fn write_header(&self, w: &mut Writer) {
+        w.seek(0);
+        self.version.write_tagged(w, Version::SONG_FILE_TAG);
+        w.write_string(&self.directory, 128);
+        w.write(self.transpose);
+
+        let mut tempo = [0; 4];
+        LittleEndian::write_f32(&mut tempo, self.tempo);
+        w.write_bytes(&tempo);
+
+        w.write(self.quantize);
+        w.write_string(&self.name, 12);
+        self.midi_settings.write(w);
+        w.write(self.key);
+        w.skip(18);
+        self.mixer_settings.write(w, self.version);
+    }
+
+    fn write_patterns(&self, ofs: &Offsets, w: &mut Writer) {
+        w.seek(ofs.groove);
+        for groove in &self.grooves {
+            groove.write(w);
+        }
+
         w.seek(ofs.song);
         w.write_bytes(&self.song.steps);
 
@@ -276,53 +439,154 @@ impl Song {
         }
     }
 
-    fn from_reader(reader: &mut Reader, version: Version) -> M8Result<Self> {
-        // TODO scale
-        let directory = reader.read_string(128);
-        let transpose = reader.read();
-        let tempo = LittleEndian::read_f32(reader.read_bytes(4));
-        let quantize = reader.read();
-        let name = reader.read_string(12);
-        let midi_settings = MidiSettings::try_from(&mut *reader)?;
-        let key = reader.read();
-        reader.read_bytes(18); // Skip
-        let mixer_settings = MixerSettings::from_reader(reader, version)?;
+    fn write_settings(&self, ofs: &Offsets, w: &mut Writer) {
+        w.seek(ofs.effect_settings);
+        self.effects_settings.write(w, self.version);
+
+        w.seek(ofs.midi_mapping);
+        for mapping in &self.midi_mappings {
+            mapping.write(w);
+        }
+
+        w.seek(ofs.bookmarks);
+        w.write_bytes(&self.song.bookmarks);
+
+        let scale_stride = Scale::song_stride(self.version);
+        for (i, scale) in self.scales.iter().enumerate() {
+            w.seek(ofs.scale + i * scale_stride);
+            scale.write(w);
+        }
+
+        if self.version.at_least(6, 5) {
+            if let Some(ppqn_offset) = ofs.groove_ppqn_offsets {
+                w.seek(ppqn_offset);
+                for groove in &self.grooves {
+                    match groove.ppqn {
+                        None => w.skip(1),
+                        Some(ppqn) => w.write(ppqn),
+                    }
+                }
+            }
+        }
+
+        if self.version.at_least(6, 6) {
+            if let (Some(row_bookmark_offset), Some(row_bookmarks)) =
+                (ofs.row_bookmark_offset, &self.song.row_bookmarks) {
+                w.seek(row_bookmark_offset);
+                w.write_bytes(row_bookmarks);
+            }
+        }
+    }
+
+    fn from_reader(reader: &mut Reader, version: Version, recovery: &mut Recovery) -> M8Result<Self> {
+        let directory = reader.read_string(128)?;
+        let transpose = reader.read()?;
+        let tempo = LittleEndian::read_f32(reader.read_bytes(4)?);
+        let quantize = reader.read()?;
+        let name = reader.read_string(12)?;
+        let midi_settings = recovery.read(
+            reader,
+            Section::Header,
+            |r| MidiSettings::try_from(r),
+            MidiSettings::default,
+        )?;
+        let key = reader.read()?;
+        reader.read_bytes(18)?; // Skip
+        let mixer_settings = recovery.read(
+            reader,
+            Section::Header,
+            |r| MixerSettings::from_reader(r, version),
+            || MixerSettings::default_ver(version),
+        )?;
 
         reader.set_pos(V4_OFFSETS.groove);
         let mut grooves = (0..Self::N_GROOVES)
-            .map(|i| Groove::from_reader(reader, i as u8))
+            .map(|i| {
+                recovery.read(
+                    reader,
+                    Section::Groove(i),
+                    |r| Groove::from_reader(r, i as u8),
+                    || Groove::default_ver(i as u8, version),
+                )
+            })
             .collect::<M8Result<Vec<Groove>>>()?;
-        let mut song = SongSteps::from_reader(reader)?;
+        let mut song = recovery.read(reader, Section::SongSteps, SongSteps::from_reader, || {
+            SongSteps::default_ver(version)
+        })?;
         let phrases = (0..Self::N_PHRASES)
-            .map(|_| Phrase::from_reader(reader, version))
+            .map(|i| {
+                recovery.read(
+                    reader,
+                    Section::Phrase(i),
+                    |r| Phrase::from_reader(r, version),
+                    || Phrase::empty(version),
+                )
+            })
             .collect::<M8Result<Vec<Phrase>>>()?;
         let chains = (0..Self::N_CHAINS)
-            .map(|_| Chain::from_reader(reader))
+            .map(|i| recovery.read(reader, Section::Chain(i), Chain::from_reader, Chain::default))
             .collect::<M8Result<Vec<Chain>>>()?;
         let tables = (0..Self::N_TABLES)
-            .map(|_| Table::from_reader(reader, version))
+            .map(|i| {
+                recovery.read(
+                    reader,
+                    Section::Table(i),
+                    |r| Table::from_reader(r, version),
+                    || Table::default_ver(version),
+                )
+            })
             .collect::<M8Result<Vec<Table>>>()?;
 
+        // a broken instrument stops anywhere in its slot
+        let instruments_start = reader.pos();
         let instruments = (0..Self::N_INSTRUMENTS)
-            .map(|i| Instrument::from_reader(reader, i as u8, version))
+            .map(|i| {
+                reader.set_pos(instruments_start + i * Instrument::INSTRUMENT_MEMORY_SIZE);
+                recovery.read(
+                    reader,
+                    Section::Instrument(i),
+                    |r| Instrument::from_reader(r, i as u8, version),
+                    || Instrument::None,
+                )
+            })
             .collect::<M8Result<Vec<Instrument>>>()?;
 
         reader.set_pos(V4_OFFSETS.effect_settings);
-        let effects_settings = EffectsSettings::from_reader(reader, version)?;
+        let effects_settings = recovery.read(
+            reader,
+            Section::EffectsSettings,
+            |r| EffectsSettings::from_reader(r, version),
+            || EffectsSettings::default_ver(version),
+        )?;
 
         reader.set_pos(V4_OFFSETS.midi_mapping);
         let midi_mappings = (0..Self::N_MIDI_MAPPINGS)
-            .map(|_| MidiMapping::from_reader(reader))
+            .map(|i| {
+                recovery.read(
+                    reader,
+                    Section::MidiMapping(i),
+                    MidiMapping::from_reader,
+                    MidiMapping::default,
+                )
+            })
             .collect::<M8Result<Vec<MidiMapping>>>()?;
 
         if reader.len() > V4_OFFSETS.bookmarks + SongSteps::ROW_COUNT {
-            song.bookmarks = reader.read_bytes(SongSteps::ROW_COUNT).try_into().unwrap();
+            song.bookmarks = reader.read_bytes(SongSteps::ROW_COUNT)?.try_into().unwrap();
         }
 
         let scales: Vec<Scale> = if version.at_least(2, 5) {
-            reader.set_pos(V4_OFFSETS.scale);
+            let scale_stride = Scale::song_stride(version);
             (0..Self::N_SCALES)
-                .map(|i| Scale::from_reader(reader, i as u8))
+                .map(|i| {
+                    reader.set_pos(V4_OFFSETS.scale + i * scale_stride);
+                    recovery.read(
+                        reader,
+                        Section::Scale(i),
+                        |r| Scale::from_reader(r, i as u8),
+                        || Scale::factory(i as u8),
+                    )
+                })
                 .collect::<M8Result<Vec<Scale>>>()?
         } else {
             (0..Self::N_SCALES)
@@ -343,8 +607,8 @@ impl Song {
 
             reader.set_pos(ofs.eq);
             (0..ofs.instrument_eq_count)
-                .map(|_i| Equ::from_reader(reader))
-                .collect::<Vec<Equ>>()
+                .map(|i| recovery.read(reader, Section::Eq(i), Equ::from_reader, Equ::empty))
+                .collect::<M8Result<Vec<Equ>>>()?
         } else {
             vec![]
         };
@@ -356,7 +620,7 @@ impl Song {
                     reader.set_pos(ppqn_offset);
 
                     for i in 0 .. grooves.len() {
-                        grooves[i].ppqn = Some(reader.read())
+                        grooves[i].ppqn = Some(reader.read()?)
                     }
                 }
             }
@@ -367,7 +631,7 @@ impl Song {
             if let Some(row_bookmark_offset) = V4_1_OFFSETS.row_bookmark_offset {
                 if reader.len() >= row_bookmark_offset + SongSteps::ROW_COUNT {
                     reader.set_pos(row_bookmark_offset);
-                    song.row_bookmarks = Some(reader.read_bytes(SongSteps::ROW_COUNT).try_into().unwrap())
+                    song.row_bookmarks = Some(reader.read_bytes(SongSteps::ROW_COUNT)?.try_into().unwrap())
                 }
             }
         }
@@ -414,6 +678,19 @@ impl SongSteps {
     pub const TRACK_COUNT: usize = 8;
     pub const ROW_COUNT: usize = 0x100;
 
+    /// Empty song steps with a given version
+    pub fn default_ver(version: Version) -> SongSteps {
+        SongSteps {
+            steps: [0xFF; SongSteps::TRACK_COUNT * SongSteps::ROW_COUNT],
+            bookmarks: [0; SongSteps::ROW_COUNT],
+            row_bookmarks: if version.at_least(6, 6) {
+                Some([0; SongSteps::ROW_COUNT])
+            } else {
+                None
+            },
+        }
+    }
+
     /// Row go from 0 to SongSetps::ROW_COUNT - 1 and track goes from 
     /// 0 to SongSteps::TRACK_COUNT - 1 here.
     pub fn is_bookmarked(&self, row: usize, track: usize) -> bool {
@@ -473,7 +750,7 @@ impl SongSteps {
 
     fn from_reader(reader: &mut Reader) -> M8Result<Self> {
         Ok(Self {
-            steps: reader.read_bytes(2048).try_into().unwrap(),
+            steps: reader.read_bytes(2048)?.try_into().unwrap(),
             bookmarks: [0; SongSteps::ROW_COUNT],
             row_bookmarks: None
         })
@@ -613,8 +890,8 @@ impl ChainStep {
 
     fn from_reader(reader: &mut Reader) -> M8Result<Self> {
         Ok(Self {
-            phrase: reader.read(),
-            transpose: reader.read(),
+            phrase: reader.read()?,
+            transpose: reader.read()?,
         })
     }
 }
@@ -625,7 +902,7 @@ impl ChainStep {
 #[derive(PartialEq, Clone, Default)]
 pub struct Phrase {
     pub steps: [Step; 16],
-    version: Version,
+    pub(crate) version: Version,
 }
 
 impl Phrase {
@@ -663,6 +940,14 @@ impl Phrase {
         }
     }
 
+    /// Phrase with only empty steps, see [`Step::empty`]
+    pub fn empty(version: Version) -> Phrase {
+        Phrase {
+            version,
+            steps: std::array::from_fn(|_| Step::empty()),
+        }
+    }
+
     pub fn clear(&mut self) {
         for s in &mut self.steps {
             s.clear();
@@ -711,6 +996,39 @@ impl Phrase {
         }
     }
 
+    /// Instrument playing the effects of every step as far as the phrase
+    /// tells, the instrument of the step or of a previous step.
+    pub fn step_instruments<'a>(&self, instruments: &'a [Instrument]) -> Vec<Option<&'a Instrument>> {
+        let mut current = None;
+        self.steps
+            .iter()
+            .map(|step| {
+                if let Some(instrument) = instruments.get(step.instrument as usize) {
+                    current = Some(instrument);
+                }
+                current
+            })
+            .collect()
+    }
+
+    /// Renumber the effects for the target version of the translation,
+    /// effects without equivalent are removed. `step_instruments` gives
+    /// the instrument playing every step, see [`Phrase::step_instruments`].
+    pub fn translate_fx(&self, translation: &FxTranslation, step_instruments: &[Option<&Instrument>]) -> Self {
+        let mut steps = self.steps.clone();
+        for (i, step) in steps.iter_mut().enumerate() {
+            let instrument = step_instruments.get(i).copied().flatten();
+            step.fx1 = translation.translate_instr_or_clear(step.fx1, instrument);
+            step.fx2 = translation.translate_instr_or_clear(step.fx2, instrument);
+            step.fx3 = translation.translate_instr_or_clear(step.fx3, instrument);
+        }
+
+        Self {
+            steps,
+            version: translation.to,
+        }
+    }
+
     pub fn write(&self, w: &mut Writer) {
         for s in &self.steps {
             s.write(w);
@@ -758,6 +1076,19 @@ pub struct Step {
 impl Step {
     pub const V4_SIZE: usize = 3 + 3 * FX::V4_SIZE;
 
+    /// Step without note, velocity, instrument or effect, as found
+    /// in a phrase freshly created on the M8.
+    pub fn empty() -> Step {
+        Step {
+            note: Note::default(),
+            velocity: 0xFF,
+            instrument: 0xFF,
+            fx1: FX::default(),
+            fx2: FX::default(),
+            fx3: FX::default(),
+        }
+    }
+
     pub fn all_fx(&self) -> [FX; 3] {
         [self.fx1, self.fx2, self.fx3]
     }
@@ -795,12 +1126,7 @@ impl Step {
     }
 
     pub fn clear(&mut self) {
-        self.note = Note::default();
-        self.velocity = 0xFF;
-        self.instrument = 0xFF;
-        self.fx1 = FX::default();
-        self.fx2 = FX::default();
-        self.fx3 = FX::default();
+        *self = Step::empty();
     }
 
     pub fn is_empty(&self) -> bool {
@@ -851,9 +1177,9 @@ impl Step {
 
     fn from_reader(reader: &mut Reader) -> M8Result<Self> {
         Ok(Self {
-            note: Note(reader.read()),
-            velocity: reader.read(),
-            instrument: reader.read(),
+            note: Note(reader.read()?),
+            velocity: reader.read()?,
+            instrument: reader.read()?,
             fx1: FX::from_reader(reader)?,
             fx2: FX::from_reader(reader)?,
             fx3: FX::from_reader(reader)?,
@@ -915,7 +1241,7 @@ impl fmt::Display for Note {
 #[derive(PartialEq, Clone)]
 pub struct Table {
     pub steps: [TableStep; 16],
-    version: Version,
+    pub(crate) version: Version,
 }
 impl Table {
     pub const V4_SIZE: usize = 16 * TableStep::V4_SIZE;
@@ -978,6 +1304,23 @@ impl Table {
         }
     }
 
+    /// Renumber the effects for the target version of the translation,
+    /// effects without equivalent are removed. `instrument` is the
+    /// instrument owning the table, if any.
+    pub fn translate_fx(&self, translation: &FxTranslation, instrument: Option<&Instrument>) -> Self {
+        let mut steps = self.steps.clone();
+        for step in &mut steps {
+            step.fx1 = translation.translate_instr_or_clear(step.fx1, instrument);
+            step.fx2 = translation.translate_instr_or_clear(step.fx2, instrument);
+            step.fx3 = translation.translate_instr_or_clear(step.fx3, instrument);
+        }
+
+        Self {
+            version: translation.to,
+            steps,
+        }
+    }
+
     pub(crate) fn print_screen(
         &self,
         f: &mut fmt::Formatter<'_>,
@@ -1188,8 +1531,8 @@ impl TableStep {
 
     fn from_reader(reader: &mut Reader) -> M8Result<Self> {
         Ok(Self {
-            transpose: reader.read(),
-            velocity: reader.read(),
+            transpose: reader.read()?,
+            velocity: reader.read()?,
             fx1: FX::from_reader(reader)?,
             fx2: FX::from_reader(reader)?,
             fx3: FX::from_reader(reader)?,
@@ -1233,10 +1576,23 @@ impl Display for Groove {
 }
 
 impl Groove {
+    /// Groove found in a new song for a given version
+    pub fn default_ver(number: u8, version: Version) -> Groove {
+        let mut steps = [0xFF; 16];
+        steps[0] = 6;
+        steps[1] = 6;
+
+        Groove {
+            number,
+            steps,
+            ppqn: if version.at_least(6, 5) { Some(0) } else { None },
+        }
+    }
+
     fn from_reader(reader: &mut Reader, number: u8) -> M8Result<Self> {
         Ok(Self {
             number,
-            steps: reader.read_bytes(16).try_into().unwrap(),
+            steps: reader.read_bytes(16)?.try_into().unwrap(),
             ppqn: None
         })
     }
@@ -1481,6 +1837,188 @@ mod tests {
         assert_eq!(test_file.mixer_settings.dj_filter_type, 0x02);
     }
 
+    #[test]
+    fn test_full_rewrite() {
+        let song_data = std::fs::read("./examples/songs/TRACKEQ.m8s").expect("Could not open TRACKEQ");
+        let mut reader = Reader::new(song_data.clone());
+        let mut song = Song::read_from_reader(&mut reader).expect("Could not parse TRACKEQ");
+
+        song.name = String::from("REWRITTEN");
+        song.tempo = 133.5;
+        song.key = 3;
+        song.grooves[1].steps[0] = 3;
+        song.scales[2].notes[4].enabled = false;
+        song.scales[2].notes[5].semitones = 1.25;
+        song.mixer_settings.master_volume = 0x42;
+        song.effects_settings.reverb_size = 0x12;
+        song.midi_mappings[3].channel = 4;
+        song.song.bookmarks[2] = 0x81;
+
+        let mut w = Writer::new(song_data);
+        song.write(&mut w).expect("Could not write TRACKEQ");
+
+        let mut reader = Reader::new(w.finish());
+        let reread = Song::read_from_reader(&mut reader).expect("Could not parse rewritten TRACKEQ");
+        assert_eq!(reread, song);
+        assert_eq!(reread.scales[1].name, "MAJOR");
+    }
+
+    /// Read then rewrite a song over its own bytes, returning the
+    /// offsets of the bytes that changed.
+    fn round_trip_differences(path: &str) -> Vec<usize> {
+        let song_data = std::fs::read(path).expect("Could not open song");
+        let mut reader = Reader::new(song_data.clone());
+        let song = Song::read_from_reader(&mut reader).expect("Could not parse song");
+
+        let mut w = Writer::new(song_data.clone());
+        song.write(&mut w).expect("Could not write song");
+        let written = w.finish();
+
+        assert_eq!(written.len(), song_data.len());
+        (0..written.len())
+            .filter(|&i| written[i] != song_data[i])
+            .collect()
+    }
+
+    #[test]
+    fn test_byte_exact_round_trip() {
+        let songs = [
+            "./examples/songs/V4EMPTY.m8s",
+            "./examples/songs/V5EMPTY.m8s",
+            "./examples/songs/V6EMPTY.m8s",
+            "./examples/songs/V6_2EMPTY.m8s",
+            "./examples/songs/V6_6EMPTY.m8s",
+            "./examples/songs/EMPTY65.m8s",
+            "./examples/songs/TRACKEQ.m8s",
+            "./examples/songs/DGLTMX.m8s",
+            "./examples/songs/GRV.m8s",
+            "./examples/songs/BOOKMARK_ON.m8s",
+            "./examples/songs/BOOKMARK_OFF.m8s",
+            "./examples/songs/Bundle/FDUB3.m8s",
+            "./examples/songs/Bundle/FDUB3_BUNDLED.m8s",
+            "./examples/songs/CommandMappingV4/CMDMAPPING_4_0.m8s",
+            "./examples/songs/CommandMappingV4/CMDMAPPING_6_0.m8s",
+            "./examples/songs/CommandMappingV4/CMDMAPPING_6_2.m8s",
+            "./examples/songs/CommandMappingV4/CMDMAPPING_6_5.m8s",
+        ];
+
+        let failures: Vec<String> = songs
+            .iter()
+            .filter_map(|path| {
+                let diffs = round_trip_differences(path);
+                if diffs.is_empty() {
+                    None
+                } else {
+                    let offsets: Vec<String> =
+                        diffs.iter().take(32).map(|o| format!("0x{o:X}")).collect();
+                    Some(format!("{path}: {} bytes differ at {}", diffs.len(), offsets.join(", ")))
+                }
+            })
+            .collect();
+
+        assert!(failures.is_empty(), "Round trip failures:\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_new_song_from_scratch() {
+        for path in ["./examples/songs/V4EMPTY.m8s", "./examples/songs/V6_6EMPTY.m8s"] {
+            let song_data = std::fs::read(path).expect("Could not open song");
+            let mut reader = Reader::new(song_data.clone());
+            let empty = Song::read_from_reader(&mut reader).expect("Could not parse song");
+
+            let mut song = Song::new(empty.version);
+            song.name = empty.name.clone();
+            assert_eq!(song, empty, "{path}");
+
+            let mut w = Writer::new_song_writer(song.version);
+            song.write(&mut w).expect("Could not write song");
+            let mut reader = Reader::new(w.finish());
+            let reread = Song::read_from_reader(&mut reader).expect("Could not parse written song");
+            assert_eq!(reread, empty, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_new_song_file_content() {
+        for path in ["./examples/songs/V4EMPTY.m8s", "./examples/songs/V6_2EMPTY.m8s", "./examples/songs/V6_6EMPTY.m8s"] {
+            assert_new_song_file_content(path);
+        }
+    }
+
+    fn assert_new_song_file_content(path: &str) {
+        let mut song_data = std::fs::read(path).expect("Could not open song");
+
+        // The directory field keeps leftovers of previously visited
+        // paths after its terminator, and the byte two places after
+        // the key changes from one device to another: these are not
+        // part of a new song.
+        let directory = 0x0E..0x0E + 128;
+        let terminator = directory.start + song_data[directory.clone()].iter().position(|&b| b == 0).unwrap();
+        song_data[terminator..directory.end].fill(0);
+        song_data[0xBD] = 0;
+
+        let mut reader = Reader::new(song_data.clone());
+        let empty = Song::read_from_reader(&mut reader).expect("Could not parse song");
+
+        // Before the limiter settings, the bytes following the mixer
+        // are leftovers varying between firmwares.
+        if !empty.version.after(&FIRMWARE_6_0_SONG_VERSION) {
+            song_data[0xEA..0xEE].fill(0);
+        }
+
+        // MIDI settings are copied from the device configuration
+        let mut song = Song::new(empty.version);
+        song.name = empty.name.clone();
+        song.midi_settings = empty.midi_settings.clone();
+
+        let mut w = Writer::new_song_writer(song.version);
+        song.write(&mut w).expect("Could not write song");
+        let written = w.finish();
+
+        let mismatch = written.iter().zip(&song_data).position(|(a, b)| a != b);
+        assert_eq!(mismatch, None, "{path}");
+        assert_eq!(written.len(), song_data.len(), "{path}");
+    }
+
+    #[test]
+    fn test_scale_round_trip() {
+        let songs = [
+            "./examples/songs/TEST-FILE.m8s",
+            "./examples/songs/V4EMPTY.m8s",
+            "./examples/songs/V6_6EMPTY.m8s",
+            "./examples/songs/TRACKEQ.m8s",
+            "./examples/songs/DGLTMX.m8s",
+        ];
+
+        for path in songs {
+            let song_data = std::fs::read(path).expect("Could not open song");
+            let mut reader = Reader::new(song_data.clone());
+            let song = Song::read_from_reader(&mut reader).expect("Could not parse song");
+            let stride = Scale::song_stride(song.version);
+
+            for scale in &song.scales {
+                let start = V4_OFFSETS.scale + scale.number as usize * stride;
+                let mut w = Writer::new(song_data.clone());
+                w.seek(start);
+                scale.write(&mut w);
+                let written = w.finish();
+                assert!(written == song_data, "{path}: scale {}", scale.number);
+
+                let mut w = Writer::new_scale_writer();
+                scale.write_file(song.version, &mut w);
+                let scale_file = w.finish();
+                assert_eq!(&scale_file[..10], b"M8VERSION\0");
+                assert_eq!(Version::from_reader(&mut Reader::new(scale_file.clone())), Ok(song.version));
+                assert_eq!(scale_file[13], Version::SCALE_FILE_TAG);
+
+                let mut reread = Scale::read(&mut scale_file.as_slice())
+                    .expect("Could not parse scale file");
+                reread.number = scale.number;
+                assert_eq!(&reread, scale, "{path}");
+            }
+        }
+    }
+
     #[test]
     fn test_song_reading() {
         let test_file = test_file();
@@ -1490,4 +2028,161 @@ mod tests {
         assert_eq!(test_file.transpose, 0x0C);
         assert_eq!(test_file.quantize, 0x02);
     }
+
+    fn read_corrupted(path: &str, patch: impl FnOnce(&mut Vec<u8>)) -> M8Result<Song> {
+        let mut data = std::fs::read(path).unwrap();
+        patch(&mut data);
+        Song::read(&mut data.as_slice())
+    }
+
+    #[test]
+    fn test_error_locations() {
+        let path = "./examples/songs/TRACKEQ.m8s";
+        let instr = V4_1_OFFSETS.instruments + Instrument::INSTRUMENT_MEMORY_SIZE;
+
+        let err = read_corrupted(path, |d| d[instr] = 0x0A).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::InvalidValue {
+                section: Section::Instrument(1),
+                offset: instr,
+                field: "instrument kind",
+                value: 0x0A
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            format!("ParseError: instrument 01: invalid instrument kind 0xA at 0x{:X}", instr)
+        );
+
+        // instrument 1 is a wavsynth, its modulators follow 33 bytes
+        // of parameters and its mod offset.
+        let modulator = instr + 33 + WavSynth::MOD_OFFSET + 2 * 6;
+        let err = read_corrupted(path, |d| d[modulator] = 0x70).unwrap_err();
+        assert_eq!(err.section(), Section::Modulator { instrument: 1, modulator: 2 });
+        assert_eq!(err.offset(), Some(modulator));
+
+        let err = read_corrupted(path, |d| d.truncate(0x100)).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Truncated {
+                section: Section::File,
+                offset: 0x100,
+                needed: Song::SIZE_PRIOR_TO_2_5 + Version::SIZE - 0x100
+            }
+        );
+
+        let eq_cut = V4_1_OFFSETS.eq + 20;
+        let err = read_corrupted(path, |d| d.truncate(eq_cut)).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Truncated { section: Section::Eq(1), offset: eq_cut, needed: 1 }
+        );
+    }
+
+    /// Every song and instrument file of the examples
+    fn example_files(dir: &std::path::Path, found: &mut Vec<std::path::PathBuf>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                example_files(&path, found);
+            } else if matches!(path.extension().and_then(|e| e.to_str()), Some("m8s" | "m8i")) {
+                found.push(path);
+            }
+        }
+    }
+
+    fn parse_example(path: &std::path::Path, data: &[u8], lenient: bool) -> bool {
+        let mut data = data;
+        if path.extension().unwrap() == "m8s" {
+            if lenient {
+                let _ = Song::read_lenient(&mut { data });
+            }
+            Song::read(&mut data).is_ok()
+        } else {
+            Instrument::read(&mut data).is_ok()
+        }
+    }
+
+    #[test]
+    fn test_truncated_files_never_panic() {
+        let mut files = vec![];
+        example_files(std::path::Path::new("./examples"), &mut files);
+        assert!(files.len() > 20);
+
+        for path in files {
+            let data = std::fs::read(&path).unwrap();
+            assert!(parse_example(&path, &data, true), "{} should parse", path.display());
+
+            // cut all along the file and at every one of the last bytes,
+            // lenient reading goes through every element so it is only
+            // tried on some of the cuts.
+            let cuts = (0..data.len())
+                .step_by(97)
+                .chain(data.len().saturating_sub(0x40)..data.len());
+            for len in cuts {
+                parse_example(&path, &data[..len], len % (97 * 16) == 0);
+            }
+
+            // same with a few bytes overwritten, using a fixed
+            // pseudo random sequence
+            let mut seed = 0x2545F491u32;
+            let mut next = || {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed as usize
+            };
+            for _ in 0..16 {
+                let mut corrupted = data.clone();
+                for _ in 0..16 {
+                    let at = next() % corrupted.len();
+                    corrupted[at] = next() as u8;
+                }
+                let len = corrupted.len() - next() % corrupted.len().min(0x200);
+                parse_example(&path, &corrupted[..len], true);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lenient_reading() {
+        let path = "./examples/songs/TRACKEQ.m8s";
+        let data = std::fs::read(path).unwrap();
+        let original = Song::read(&mut data.as_slice()).unwrap();
+
+        let (song, diagnostics) = Song::read_lenient(&mut data.as_slice()).unwrap();
+        assert_eq!(song, original);
+        assert!(diagnostics.is_empty());
+
+        let instr = V4_1_OFFSETS.instruments + Instrument::INSTRUMENT_MEMORY_SIZE;
+        let modulator = instr + 33 + WavSynth::MOD_OFFSET;
+        let mut corrupted = data.clone();
+        corrupted[modulator] = 0x70;
+        corrupted[V4_1_OFFSETS.instruments + 3 * Instrument::INSTRUMENT_MEMORY_SIZE] = 0x0A;
+
+        let (song, diagnostics) = Song::read_lenient(&mut corrupted.as_slice()).unwrap();
+        let sections: Vec<Section> = diagnostics.iter().map(|d| d.error.section()).collect();
+        assert_eq!(
+            sections,
+            vec![Section::Modulator { instrument: 1, modulator: 0 }, Section::Instrument(3)]
+        );
+        assert_eq!(song.instruments[1], Instrument::None);
+        assert_eq!(song.instruments[2], original.instruments[2]);
+        assert_eq!(song.instruments[3], Instrument::None);
+        assert_eq!(song.instruments[4], original.instruments[4]);
+        assert!(song.phrases == original.phrases);
+        assert_eq!(song.eqs, original.eqs);
+
+        // truncation is reported once, elements before it are kept
+        let cut = V4_1_OFFSETS.table + 10;
+        let (song, diagnostics) = Song::read_lenient(&mut &data[..cut]).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].error.section(), Section::Table(0));
+        assert!(song.chains == original.chains);
+        assert_eq!(song.instruments[1], Instrument::None);
+        assert_eq!(song.eqs[1], Equ::empty());
+
+        assert!(Song::read_lenient(&mut &data[..0x40]).is_err());
+    }
 }
@@ -0,0 +1,15 @@
+//! Helpers shared by the unit tests.
+use crate::fx::FX;
+use crate::songs::Song;
+use std::fs::File;
+
+pub(crate) fn read_song(path: &str) -> Song {
+    let mut f = File::open(path).expect("Could not open song");
+    Song::read(&mut f).expect("Could not parse song")
+}
+
+/// Effect with the command of the given name in the song version
+pub(crate) fn fx(song: &Song, command: &str, value: u8) -> FX {
+    let command = FX::fx_command_names(song.version).find_indices(&[command])[0];
+    FX { command, value }
+}
@@ -1,5 +1,6 @@
 use crate::reader::*;
 use crate::version::*;
+use crate::writer::Writer;
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Theme {
@@ -18,21 +19,69 @@ pub struct Theme {
     pub meter_peak: RGB,
 }
 impl Theme {
-    const SIZE: usize = 39;
+    pub const SIZE: usize = 39;
 
-    pub fn read(reader: &mut impl std::io::Read) -> M8Result<Self> {
-        let mut buf: Vec<u8> = vec![];
-        reader.read_to_end(&mut buf).unwrap();
-        let len = buf.len();
-        let mut reader = Reader::new(buf);
-
-        if len < Self::SIZE + Version::SIZE {
-            return Err(ParseError(
-                "File is not long enough to be a M8 Theme".to_string(),
-            ));
+    /// Number of colors in a theme
+    pub const COLOR_COUNT: usize = 13;
+
+    /// Build a theme from its colors, in the order of the fields
+    /// (from `background` to `meter_peak`).
+    pub fn new(colors: [RGB; Theme::COLOR_COUNT]) -> Self {
+        let [background, text_empty, text_info, text_default, text_value, text_title,
+             play_marker, cursor, selection, scope_slider, meter_low, meter_mid, meter_peak] = colors;
+
+        Self {
+            background,
+            text_empty,
+            text_info,
+            text_default,
+            text_value,
+            text_title,
+            play_marker,
+            cursor,
+            selection,
+            scope_slider,
+            meter_low,
+            meter_mid,
+            meter_peak,
         }
+    }
+
+    /// Colors of the theme, in the order they are stored
+    pub fn colors(&self) -> [RGB; Theme::COLOR_COUNT] {
+        [
+            self.background,
+            self.text_empty,
+            self.text_info,
+            self.text_default,
+            self.text_value,
+            self.text_title,
+            self.play_marker,
+            self.cursor,
+            self.selection,
+            self.scope_slider,
+            self.meter_low,
+            self.meter_mid,
+            self.meter_peak,
+        ]
+    }
+
+    pub fn read(reader: &mut impl std::io::Read) -> M8Result<Self> {
+        let mut reader = Reader::from_read(reader)?;
+
+        reader.expect_len(Self::SIZE + Version::SIZE)?;
         Version::from_reader(&mut reader)?;
-        Self::from_reader(&mut reader)
+        Self::from_reader(&mut reader).map_err(|e| e.in_section(Section::Theme))
+    }
+
+    /// Write the theme as a theme file for the given version, to be
+    /// used with a writer from [`Writer::new_theme_writer`].
+    pub fn write(&self, version: Version, w: &mut Writer) {
+        w.seek(0);
+        version.write_tagged(w, Version::THEME_FILE_TAG);
+        for color in self.colors() {
+            color.write(w);
+        }
     }
 
     fn from_reader(reader: &mut Reader) -> M8Result<Self> {
@@ -62,11 +111,51 @@ pub struct RGB {
 }
 
 impl RGB {
+    pub fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    pub fn write(&self, w: &mut Writer) {
+        w.write(self.r);
+        w.write(self.g);
+        w.write(self.b);
+    }
+
     fn from_reader(reader: &mut Reader) -> M8Result<Self> {
         Ok(Self {
-            r: reader.read(),
-            g: reader.read(),
-            b: reader.read(),
+            r: reader.read()?,
+            g: reader.read()?,
+            b: reader.read()?,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::theme::*;
+
+    fn theme() -> Theme {
+        let mut colors = [RGB::new(0, 0, 0); Theme::COLOR_COUNT];
+        for (i, color) in colors.iter_mut().enumerate() {
+            let i = i as u8;
+            *color = RGB::new(i, 0x40 + i, 0x80 + i);
+        }
+        Theme::new(colors)
+    }
+
+    #[test]
+    fn test_theme_write_layout() {
+        let theme = theme();
+        let mut w = Writer::new_theme_writer();
+        theme.write(Version::new(6, 6), &mut w);
+        let written = w.finish();
+
+        assert_eq!(written.len(), Version::SIZE + Theme::SIZE);
+        assert_eq!(&written[..Version::SIZE], b"M8VERSION\0\x60\x06\x00\x20");
+        assert_eq!(&written[Version::SIZE..Version::SIZE + 6], &[0x00, 0x40, 0x80, 0x01, 0x41, 0x81]);
+        assert_eq!(&written[written.len() - 3..], &[0x0C, 0x4C, 0x8C]);
+
+        let reread = Theme::read(&mut written.as_slice()).expect("Could not parse theme");
+        assert_eq!(reread, theme);
+    }
+}
@@ -0,0 +1,479 @@
+//! Playback simulation of a song, resolving song rows, chains
+//! and phrases into timed events.
+//!
+//! Each track plays its song rows until the first empty one, and
+//! each chain until its first empty step. Step lengths follow the
+//! groove of the track, which moves forward once per played step
+//! whatever the phrase step is. The tempo is shared by all tracks.
+//! Tables are not simulated.
+//!
+//! Sequencer commands are interpreted as follows (all durations
+//! are in ticks of the active groove):
+//!  - `HOP xx` jumps to step xx of the next phrase, `HOP FF` stops the track
+//!  - `KIL xx` stops the note after xx ticks
+//!  - `DEL xx` delays the step by xx ticks, it is not played if the
+//!    delay is longer than the step
+//!  - `RET xy` retriggers the note every y ticks until the next step
+//!  - `REP xy` repeats the note x times, every y ticks
+//!
+//! Notes and note offs generated by `KIL`, `RET` and `REP` past the
+//! end of their step are dropped.
+//!  - `TIC xx` sets the tick rate of the table, it is ignored
+//!  - `GRV xx` switches the track to groove xx
+//!  - `TPO xx` sets the tempo to xx BPM
+use crate::fx::*;
+use crate::songs::*;
+
+/// Position in the played song
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct TimelinePosition {
+    /// Time since the song start
+    pub seconds: f64,
+
+    /// Quarter notes since the song start, independent of
+    /// tempo changes.
+    pub beats: f64,
+}
+
+impl TimelinePosition {
+    fn advance(self, ticks: f64, ppqn: f64, tempo: f64) -> Self {
+        Self {
+            seconds: self.seconds + ticks * 60.0 / (tempo * ppqn),
+            beats: self.beats + ticks / ppqn,
+        }
+    }
+}
+
+/// Played step, or note generated by a sequencer command
+#[derive(PartialEq, Debug, Clone)]
+pub struct TimelineEvent {
+    pub position: TimelinePosition,
+    pub track: usize,
+
+    /// Transposed note, `Note(0x80)` for note off
+    pub note: Note,
+
+    /// Velocity of the step, 0xFF if not set
+    pub velocity: u8,
+
+    /// Last instrument set on the track, 0xFF if none
+    pub instrument: u8,
+
+    /// Effects of the played step, empty for generated notes
+    pub fx: [FX; 3],
+}
+
+/// Events of a played song, ordered by time then track
+#[derive(PartialEq, Debug, Clone)]
+pub struct Timeline {
+    pub events: Vec<TimelineEvent>,
+
+    /// Position where each track stopped playing
+    pub track_ends: [TimelinePosition; SongSteps::TRACK_COUNT],
+}
+
+impl Timeline {
+    /// Length of the song, up to the end of the last playing track
+    pub fn duration(&self) -> TimelinePosition {
+        self.track_ends
+            .iter()
+            .copied()
+            .fold(TimelinePosition::default(), |acc, end| {
+                if end.seconds > acc.seconds { end } else { acc }
+            })
+    }
+}
+
+/// Playing position of a track
+struct TrackCursor {
+    track: usize,
+    row: usize,
+    chain_step: usize,
+    phrase_step: usize,
+    groove: usize,
+
+    /// Steps played so far, giving the groove step
+    played_steps: usize,
+    instrument: u8,
+    position: TimelinePosition,
+    stopped: bool,
+}
+
+impl TrackCursor {
+    /// Chain step being played (with its transposition) and its phrase,
+    /// moving to the next row at the end of a chain.
+    fn locate<'a>(&mut self, song: &'a Song) -> Option<(u8, &'a Phrase)> {
+        while !self.stopped {
+            if self.row >= SongSteps::ROW_COUNT {
+                self.stopped = true;
+                break;
+            }
+
+            let chain = song.song.steps[self.row * SongSteps::TRACK_COUNT + self.track];
+            let Some(chain) = song.chains.get(chain as usize) else {
+                self.stopped = true;
+                break;
+            };
+
+            let phrase = chain
+                .steps
+                .get(self.chain_step)
+                .and_then(|step| song.phrases.get(step.phrase as usize).map(|p| (step.transpose, p)));
+
+            match phrase {
+                Some(found) => return Some(found),
+                None => {
+                    self.row += 1;
+                    self.chain_step = 0;
+                    self.phrase_step = 0;
+                }
+            }
+        }
+
+        None
+    }
+
+    fn next_step(&mut self, hop: Option<u8>) {
+        match hop {
+            Some(0xFF) => self.stopped = true,
+            Some(step) => {
+                self.phrase_step = (step as usize) % 16;
+                self.chain_step += 1;
+            }
+            None => {
+                self.phrase_step += 1;
+                if self.phrase_step >= 16 {
+                    self.phrase_step = 0;
+                    self.chain_step += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Sequencer commands of a step, by name
+struct StepCommands {
+    hop: Option<u8>,
+    kill: Option<u8>,
+    delay: u8,
+    retrigger: u8,
+    repeat: Option<(u8, u8)>,
+    groove: Option<u8>,
+    tempo: Option<u8>,
+}
+
+impl StepCommands {
+    fn from_step(step: &Step, commands: FxCommands) -> Self {
+        let mut cmds = StepCommands {
+            hop: None,
+            kill: None,
+            delay: 0,
+            retrigger: 0,
+            repeat: None,
+            groove: None,
+            tempo: None,
+        };
+
+        for fx in step.all_fx() {
+            match commands.try_render(fx.command) {
+                Some("HOP") => cmds.hop = Some(fx.value),
+                Some("KIL") => cmds.kill = Some(fx.value),
+                Some("DEL") => cmds.delay = fx.value,
+                Some("RET") => cmds.retrigger = fx.value & 0x0F,
+                Some("REP") => cmds.repeat = Some((fx.value >> 4, fx.value & 0x0F)),
+                Some("GRV") => cmds.groove = Some(fx.value),
+                Some("TPO") if fx.value > 0 => cmds.tempo = Some(fx.value),
+                _ => {}
+            }
+        }
+
+        cmds
+    }
+}
+
+impl Song {
+    /// Simulate the playback of the whole song
+    pub fn timeline(&self) -> Timeline {
+        let commands = FX::fx_command_names(self.version);
+        let mut tempo = f64::from(self.tempo).max(1.0);
+        let mut events = vec![];
+        let mut cursors: Vec<TrackCursor> = (0..SongSteps::TRACK_COUNT)
+            .map(|track| TrackCursor {
+                track,
+                row: 0,
+                chain_step: 0,
+                phrase_step: 0,
+                groove: 0,
+                played_steps: 0,
+                instrument: 0xFF,
+                position: TimelinePosition::default(),
+                stopped: false,
+            })
+            .collect();
+
+        loop {
+            // next track to play is the one furthest behind in time
+            let next = cursors
+                .iter_mut()
+                .filter(|c| !c.stopped)
+                .fold(None, |acc: Option<&mut TrackCursor>, c| match acc {
+                    Some(best) if best.position.seconds <= c.position.seconds => Some(best),
+                    _ => Some(c),
+                });
+            let Some(cursor) = next else { break };
+            let Some((transpose, phrase)) = cursor.locate(self) else { continue };
+
+            let step = &phrase.steps[cursor.phrase_step];
+            let cmds = StepCommands::from_step(step, commands);
+
+            if let Some(groove) = cmds.groove {
+                if (groove as usize) < self.grooves.len() {
+                    cursor.groove = groove as usize;
+                }
+            }
+
+            if let Some(bpm) = cmds.tempo {
+                tempo = f64::from(bpm);
+            }
+
+            let groove = &self.grooves[cursor.groove];
+            let ppqn = groove.active_ppqn() as f64;
+            let groove_steps = groove.active_steps();
+            let ticks = if groove_steps.is_empty() {
+                6
+            } else {
+                groove_steps[cursor.played_steps % groove_steps.len()]
+            };
+
+            if (step.instrument as usize) < Song::N_INSTRUMENTS {
+                cursor.instrument = step.instrument;
+            }
+
+            let has_fx = step.all_fx().iter().any(|fx| !fx.is_empty());
+            if cmds.delay < ticks && (!step.note.is_empty() || has_fx) {
+                let note = if step.note.0 < 0x80 {
+                    let transpose = transpose as i8 as i16 + self.transpose as i8 as i16;
+                    Note((step.note.0 as i16 + transpose).clamp(0, 0x7F) as u8)
+                } else {
+                    step.note
+                };
+
+                let at = |tick: u32| cursor.position.advance(f64::from(tick), ppqn, tempo);
+                let start = u32::from(cmds.delay);
+                let generated = |tick: u32, note: Note| TimelineEvent {
+                    position: at(tick),
+                    track: cursor.track,
+                    note,
+                    velocity: step.velocity,
+                    instrument: cursor.instrument,
+                    fx: [FX::default(); 3],
+                };
+
+                events.push(TimelineEvent {
+                    fx: step.all_fx(),
+                    ..generated(start, note)
+                });
+
+                // generated notes stop at the end of the step
+                let end = u32::from(ticks);
+                if note.0 < 0x80 {
+                    let retrigger = u32::from(cmds.retrigger);
+                    if retrigger > 0 {
+                        let mut tick = start + retrigger;
+                        while tick < end {
+                            events.push(generated(tick, note));
+                            tick += retrigger;
+                        }
+                    }
+
+                    if let Some((count, every)) = cmds.repeat {
+                        let repeats = (1..=u32::from(count)).map(|i| start + i * u32::from(every));
+                        for tick in repeats.take_while(|&tick| tick < end) {
+                            events.push(generated(tick, note));
+                        }
+                    }
+
+                    if let Some(kill) = cmds.kill.map(|kill| start + u32::from(kill)) {
+                        if kill < end {
+                            events.push(generated(kill, Note(0x80)));
+                        }
+                    }
+                }
+            }
+
+            cursor.position = cursor.position.advance(f64::from(ticks), ppqn, tempo);
+            cursor.played_steps += 1;
+            cursor.next_step(cmds.hop);
+        }
+
+        events.sort_by(|a, b| {
+            a.position
+                .seconds
+                .total_cmp(&b.position.seconds)
+                .then(a.track.cmp(&b.track))
+        });
+
+        let mut track_ends = [TimelinePosition::default(); SongSteps::TRACK_COUNT];
+        for cursor in &cursors {
+            track_ends[cursor.track] = cursor.position;
+        }
+
+        Timeline { events, track_ends }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timeline::*;
+    use crate::Version;
+
+    const DEL: u8 = 0x02;
+    const GRV: u8 = 0x03;
+    const HOP: u8 = 0x04;
+    const KIL: u8 = 0x05;
+    const RET: u8 = 0x08;
+    const REP: u8 = 0x09;
+    const TIC: u8 = 0x16;
+    const TPO: u8 = 0x18;
+
+    fn one_phrase_song() -> Song {
+        let mut song = Song::new(Version::new(6, 6));
+        song.chains[0].steps[0].phrase = 0;
+        song.song.steps[0] = 0;
+        song
+    }
+
+    fn notes(timeline: &Timeline) -> Vec<(f64, u8)> {
+        timeline
+            .events
+            .iter()
+            .map(|e| (e.position.beats * 4.0, e.note.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_plain_playback() {
+        let mut song = one_phrase_song();
+        song.phrases[0].steps[0].note = Note(0x30);
+        song.phrases[0].steps[0].instrument = 3;
+        song.phrases[0].steps[4].note = Note(0x80);
+        song.chains[0].steps[1].phrase = 0;
+        song.chains[0].steps[1].transpose = 0x0C;
+        song.song.steps[SongSteps::TRACK_COUNT] = 0;
+        song.song.steps[1] = 0;
+
+        let timeline = song.timeline();
+        assert_eq!(
+            notes(&timeline),
+            vec![
+                (0.0, 0x30), (0.0, 0x30),
+                (4.0, 0x80), (4.0, 0x80),
+                (16.0, 0x3C), (16.0, 0x3C),
+                (20.0, 0x80), (20.0, 0x80),
+                (32.0, 0x30),
+                (36.0, 0x80),
+                (48.0, 0x3C),
+                (52.0, 0x80),
+            ]
+        );
+        assert_eq!(timeline.events[0].track, 0);
+        assert_eq!(timeline.events[1].track, 1);
+        assert_eq!(timeline.events[0].instrument, 3);
+
+        // 4 phrases of 16 sixteenth at 120 BPM
+        assert_eq!(timeline.duration().beats, 16.0);
+        assert_eq!(timeline.duration().seconds, 8.0);
+        assert_eq!(timeline.track_ends[1].beats, 8.0);
+        assert_eq!(timeline.track_ends[2].beats, 0.0);
+    }
+
+    #[test]
+    fn test_note_commands() {
+        let mut song = one_phrase_song();
+        let steps = &mut song.phrases[0].steps;
+        steps[0].note = Note(0x30);
+        steps[0].fx1 = FX { command: DEL, value: 3 };
+        steps[0].fx2 = FX { command: KIL, value: 2 };
+        steps[1].note = Note(0x31);
+        steps[1].fx1 = FX { command: DEL, value: 6 };
+        steps[2].note = Note(0x32);
+        steps[2].fx1 = FX { command: RET, value: 0x02 };
+        steps[3].note = Note(0x33);
+        steps[3].fx1 = FX { command: REP, value: 0x23 };
+
+        let timeline = song.timeline();
+        let ticks: Vec<(f64, u8)> = timeline
+            .events
+            .iter()
+            .map(|e| (e.position.beats * 24.0, e.note.0))
+            .collect();
+
+        assert_eq!(
+            ticks,
+            vec![
+                (3.0, 0x30),
+                (5.0, 0x80),
+                (12.0, 0x32),
+                (14.0, 0x32),
+                (16.0, 0x32),
+                // second repeat would fall on the next step
+                (18.0, 0x33),
+                (21.0, 0x33),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flow_commands() {
+        let mut song = one_phrase_song();
+        song.phrases[0].steps[0].note = Note(0x30);
+        song.phrases[0].steps[0].fx1 = FX { command: TIC, value: 12 };
+        song.phrases[0].steps[1].fx1 = FX { command: HOP, value: 14 };
+        song.phrases[0].steps[14].note = Note(0x32);
+        song.phrases[0].steps[15].fx1 = FX { command: TPO, value: 60 };
+        song.phrases[0].steps[15].fx2 = FX { command: GRV, value: 1 };
+        song.grooves[1].steps[0] = 12;
+        song.grooves[1].steps[1] = 12;
+        song.chains[0].steps[1].phrase = 0;
+        song.chains[0].steps[2].phrase = 0;
+
+        let timeline = song.timeline();
+        let played: Vec<(f64, f64, u8)> = timeline
+            .events
+            .iter()
+            .filter(|e| !e.note.is_empty())
+            .map(|e| (e.position.beats * 24.0, e.position.seconds, e.note.0))
+            .collect();
+
+        // TIC only changes the table speed, second phrase starts at
+        // step 14, the tempo and groove change on its last step
+        assert_eq!(played[0], (0.0, 0.0, 0x30));
+        assert_eq!(played[1], (12.0, 0.25, 0x32));
+        assert_eq!(played[2], (30.0, 0.875, 0x30));
+
+        // HOP FF stops the track
+        song.phrases[0].steps[1].fx1 = FX { command: HOP, value: 0xFF };
+        let timeline = song.timeline();
+        assert_eq!(timeline.track_ends[0].beats * 24.0, 12.0);
+    }
+
+    #[test]
+    fn test_groove_follows_played_steps() {
+        let mut song = one_phrase_song();
+        song.grooves[0].steps[0] = 8;
+        song.grooves[0].steps[1] = 4;
+        song.phrases[0].steps[0].note = Note(0x30);
+        song.phrases[0].steps[0].fx1 = FX { command: HOP, value: 2 };
+        song.phrases[0].steps[2].note = Note(0x32);
+        song.phrases[0].steps[3].note = Note(0x33);
+        song.chains[0].steps[1].phrase = 0;
+
+        // the hop lands on the second groove step, whatever the
+        // phrase step is
+        let timeline = song.timeline();
+        assert_eq!(
+            notes(&timeline)[..3].iter().map(|&(t, n)| (t * 6.0, n)).collect::<Vec<_>>(),
+            vec![(0.0, 0x30), (8.0, 0x32), (12.0, 0x33)]
+        );
+    }
+}
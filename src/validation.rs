@@ -0,0 +1,236 @@
+//! Detection of dubious content in a song.
+use crate::fx::*;
+use crate::instruments::*;
+use crate::remapper::{
+    EQ_TRACKING_COMMAND_NAMES, INSTRUMENT_TRACKING_COMMAND_NAMES, TABLE_TRACKING_COMMAND_NAMES,
+};
+use crate::songs::*;
+
+/// Where an effect was found
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum FxLocation {
+    Phrase { phrase: usize, step: usize },
+    Table { table: usize, step: usize },
+}
+
+/// Problem found by [`Song::validate`]. None of them prevents the song
+/// from loading, but they are likely to be mistakes.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ValidationWarning {
+    /// Phrase step playing an empty instrument slot
+    EmptyInstrument { phrase: usize, step: usize, instrument: u8 },
+
+    /// Chain step pointing to a phrase without any content
+    EmptyPhrase { chain: usize, step: usize, phrase: u8 },
+
+    /// INS/NXT, TBX/TBL or EQI/EQM effect whose value is an empty
+    /// instrument slot, an unused table or a missing EQ.
+    DanglingReference { location: FxLocation, command: &'static str, value: u8 },
+
+    /// Instrument EQ past the EQ count of the song
+    InstrumentEqOutOfRange { instrument: usize, eq: u8 },
+
+    /// The first step of the groove is disabled, no step is played
+    EmptyGroove { groove: usize },
+
+    /// Every note of the scale is disabled
+    EmptyScale { scale: usize },
+
+    /// Sampler instrument without any sample
+    MissingSample { instrument: usize },
+}
+
+/// Reference effects of a song version, by kind of target
+struct ReferenceCommands {
+    fx_commands: FxCommands,
+    instrument: Vec<u8>,
+    table: Vec<u8>,
+    eq: Vec<u8>,
+}
+
+impl ReferenceCommands {
+    fn new(song: &Song) -> Self {
+        let fx_commands = FX::fx_command_names(song.version);
+        Self {
+            fx_commands,
+            instrument: fx_commands.find_indices(&INSTRUMENT_TRACKING_COMMAND_NAMES),
+            table: fx_commands.find_indices(&TABLE_TRACKING_COMMAND_NAMES),
+            eq: fx_commands.find_indices(&EQ_TRACKING_COMMAND_NAMES),
+        }
+    }
+
+    /// Tell if the effect references something missing in the song
+    fn is_dangling(&self, song: &Song, fx: FX) -> bool {
+        let target = fx.value as usize;
+        if self.instrument.contains(&fx.command) {
+            target >= song.instruments.len() || song.instruments[target].is_empty()
+        } else if self.table.contains(&fx.command) {
+            !is_table_allocated(song, target)
+        } else if self.eq.contains(&fx.command) {
+            // FF removes the EQ
+            fx.value != 0xFF && target >= song.eqs.len()
+        } else {
+            false
+        }
+    }
+
+    fn check(&self, song: &Song, location: FxLocation, fxs: [FX; 3], out: &mut Vec<ValidationWarning>) {
+        for fx in fxs {
+            if self.is_dangling(song, fx) {
+                out.push(ValidationWarning::DanglingReference {
+                    location,
+                    command: self.fx_commands.try_render(fx.command).unwrap_or("?"),
+                    value: fx.value,
+                });
+            }
+        }
+    }
+}
+
+/// A table is in use if it has content or belongs to an instrument
+fn is_table_allocated(song: &Song, table: usize) -> bool {
+    let has_instrument = song.instruments.get(table).is_some_and(|i| !i.is_empty());
+    song.tables.get(table).is_some_and(|t| !t.is_empty()) || has_instrument
+}
+
+impl Song {
+    /// Look for likely mistakes: references to empty slots, EQs out
+    /// of range, unplayable grooves or scales, samplers without sample.
+    pub fn validate(&self) -> Vec<ValidationWarning> {
+        let mut out = vec![];
+        let references = ReferenceCommands::new(self);
+
+        for (phrase_id, phrase) in self.phrases.iter().enumerate() {
+            for (step_id, step) in phrase.steps.iter().enumerate() {
+                let instrument = step.instrument as usize;
+                if instrument < Song::N_INSTRUMENTS && self.instruments[instrument].is_empty() {
+                    out.push(ValidationWarning::EmptyInstrument {
+                        phrase: phrase_id,
+                        step: step_id,
+                        instrument: step.instrument,
+                    });
+                }
+
+                let location = FxLocation::Phrase { phrase: phrase_id, step: step_id };
+                references.check(self, location, step.all_fx(), &mut out);
+            }
+        }
+
+        for (chain_id, chain) in self.chains.iter().enumerate() {
+            for (step_id, step) in chain.steps.iter().enumerate() {
+                let phrase = step.phrase as usize;
+                if phrase < Song::N_PHRASES && self.phrases[phrase].is_empty() {
+                    out.push(ValidationWarning::EmptyPhrase {
+                        chain: chain_id,
+                        step: step_id,
+                        phrase: step.phrase,
+                    });
+                }
+            }
+        }
+
+        for (table_id, table) in self.tables.iter().enumerate() {
+            for (step_id, step) in table.steps.iter().enumerate() {
+                let location = FxLocation::Table { table: table_id, step: step_id };
+                references.check(self, location, step.all_fx(), &mut out);
+            }
+        }
+
+        // songs prior to 4.0 have no EQ at all
+        if !self.eqs.is_empty() {
+            for (instrument, instr) in self.instruments.iter().enumerate() {
+                match instr.equ() {
+                    Some(eq) if eq != 0xFF && eq as usize >= self.eq_count() => {
+                        out.push(ValidationWarning::InstrumentEqOutOfRange { instrument, eq })
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for (groove, g) in self.grooves.iter().enumerate() {
+            if g.active_steps().is_empty() {
+                out.push(ValidationWarning::EmptyGroove { groove });
+            }
+        }
+
+        for (scale, s) in self.scales.iter().enumerate() {
+            if s.notes.iter().all(|n| !n.enabled) {
+                out.push(ValidationWarning::EmptyScale { scale });
+            }
+        }
+
+        for (instrument, instr) in self.instruments.iter().enumerate() {
+            if let Instrument::Sampler(s) = instr {
+                if s.sample_path.is_empty() {
+                    out.push(ValidationWarning::MissingSample { instrument });
+                }
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{fx, read_song};
+    use crate::validation::*;
+    use crate::Version;
+
+    #[test]
+    fn test_clean_songs() {
+        for path in ["./examples/songs/V4EMPTY.m8s", "./examples/songs/V6_6EMPTY.m8s"] {
+            assert_eq!(read_song(path).validate(), vec![]);
+        }
+    }
+
+    #[test]
+    fn test_validation_warnings() {
+        let mut song = read_song("./examples/songs/V6EMPTY.m8s");
+        let trackeq = read_song("./examples/songs/TRACKEQ.m8s");
+        let test_file = read_song("./examples/songs/TEST-FILE.m8s");
+        assert_eq!(song.version, Version { major: 6, minor: 0, patch: 1 });
+
+        song.instruments[1] = trackeq.instruments[1].clone();
+        song.instruments[1].set_eq(0x90);
+        song.instruments[3] = test_file.instruments[3].clone();
+        if let Instrument::Sampler(s) = &mut song.instruments[3] {
+            s.sample_path.clear();
+        }
+
+        song.phrases[0].steps[0].instrument = 5;
+        song.phrases[0].steps[1].fx1 = fx(&song, "INS", 0x07);
+        song.phrases[0].steps[1].fx2 = fx(&song, "TBX", 0x90);
+        song.phrases[0].steps[1].fx3 = fx(&song, "EQI", 0xFF);
+        song.phrases[0].steps[2].fx1 = fx(&song, "INS", 0x01);
+        song.tables[3].steps[4].fx1 = fx(&song, "EQI", 0x80);
+        song.chains[0].steps[0].phrase = 3;
+        // steps past the first disabled one are never played
+        song.grooves[2].steps = [0xFF; 16];
+        song.grooves[2].steps[1] = 6;
+        for note in song.scales[4].notes.iter_mut() {
+            note.enabled = false;
+        }
+
+        let phrase = |step| FxLocation::Phrase { phrase: 0, step };
+        assert_eq!(
+            song.validate(),
+            vec![
+                ValidationWarning::EmptyInstrument { phrase: 0, step: 0, instrument: 5 },
+                ValidationWarning::DanglingReference { location: phrase(1), command: "INS", value: 0x07 },
+                ValidationWarning::DanglingReference { location: phrase(1), command: "TBX", value: 0x90 },
+                ValidationWarning::EmptyPhrase { chain: 0, step: 0, phrase: 3 },
+                ValidationWarning::DanglingReference {
+                    location: FxLocation::Table { table: 3, step: 4 },
+                    command: "EQI",
+                    value: 0x80
+                },
+                ValidationWarning::InstrumentEqOutOfRange { instrument: 1, eq: 0x90 },
+                ValidationWarning::EmptyGroove { groove: 2 },
+                ValidationWarning::EmptyScale { scale: 4 },
+                ValidationWarning::MissingSample { instrument: 3 },
+            ]
+        );
+    }
+}
@@ -55,25 +55,43 @@ impl Version {
         Version { major, minor, patch: 0 }
     }
 
+    /// Tag written after the version in instrument files
+    pub const INSTRUMENT_FILE_TAG: u8 = 0x10;
+
+    /// Tag written after the version in song files
+    pub const SONG_FILE_TAG: u8 = 0x00;
+
+    /// Tag written after the version in theme files
+    pub const THEME_FILE_TAG: u8 = 0x20;
+
+    /// Tag written after the version in scale files
+    pub const SCALE_FILE_TAG: u8 = 0x30;
+
     pub fn write(&self, w: &mut Writer) {
+        // why? don't know, but borked result if not written
+        self.write_tagged(w, Version::INSTRUMENT_FILE_TAG)
+    }
+
+    /// Write the version header, followed by the file kind tag
+    pub fn write_tagged(&self, w: &mut Writer, tag: u8) {
         w.write_string("M8VERSION", 10);
 
         w.write((self.minor << 4) | self.patch);
         w.write(self.major);
 
         w.write(0);
-        w.write(0x10); // why? don't know, but borked result if not written
+        w.write(tag);
     }
 
     pub fn from_reader(reader: &mut Reader) -> M8Result<Self> {
-        let _version_string = reader.read_bytes(10);
-        let lsb = reader.read();
-        let msb = reader.read();
+        let _version_string = reader.read_bytes(10)?;
+        let lsb = reader.read()?;
+        let msb = reader.read()?;
         let major = msb & 0x0F;
         let minor = (lsb >> 4) & 0x0F;
         let patch = lsb & 0x0F;
 
-        reader.read_bytes(2); // Skip
+        reader.read_bytes(2)?; // Skip
         Ok(Self {
             major,
             minor,
@@ -1,3 +1,9 @@
+use crate::reader::decode_string;
+use crate::scale::Scale;
+use crate::songs::Song;
+use crate::theme::Theme;
+use crate::version::Version;
+
 pub struct Writer {
     buffer: Vec<u8>,
     pos: usize,
@@ -8,7 +14,11 @@ impl Writer {
     pub const INSTRUMENT_FILE_SIZE : usize = 357;
     pub const INSTRUMENT_FILE_SIZE_WITH_EQ : usize = 375;
 
-    /// Initialize the writer from a loaded song
+    /// Initialize the writer from a loaded song.
+    ///
+    /// Only the elements parsed by the library get overwritten, every
+    /// other byte is carried through unchanged: writing back an
+    /// unmodified song gives the exact same file.
     pub fn new(v: Vec<u8>) -> Writer {
         Writer { buffer: v, pos: 0 }
     }
@@ -21,6 +31,31 @@ impl Writer {
         Writer { buffer: vec![0; file_size], pos: 0 }
     }
 
+    /// Initialize a writer for a standalone scale file
+    pub fn new_scale_writer() -> Writer {
+        Writer { buffer: vec![0; Version::SIZE + Scale::V4_SIZE], pos: 0 }
+    }
+
+    /// Initialize a writer for a theme file
+    pub fn new_theme_writer() -> Writer {
+        Writer { buffer: vec![0; Version::SIZE + Theme::SIZE], pos: 0 }
+    }
+
+    /// Initialize a writer with an empty song file for the layout
+    /// of the given version, to write a song without starting from
+    /// an existing file.
+    pub fn new_song_writer(version: Version) -> Writer {
+        Writer::new(Song::blank_file(version))
+    }
+
+    /// Initialize a writer for a song converted with [`Song::migrate_to`]
+    /// or [`Song::downgrade_to`], from the content of the file it was
+    /// read from. The effect and global EQs, not parsed, and the bytes
+    /// past the known layout are kept, the rest starts empty.
+    pub fn new_converted_song_writer(original: &[u8], version: Version) -> Result<Writer, String> {
+        Song::converted_file(original, version).map(Writer::new)
+    }
+
     /// Terminate writing and return the buffer
     pub fn finish(self) -> Vec<u8> {
         self.buffer
@@ -43,8 +78,24 @@ impl Writer {
         self.pos = cursor;
     }
 
+    pub fn write_bool(&mut self, v: bool) {
+        self.write(if v { 1 } else { 0 });
+    }
+
+    /// Write a string in a fixed size field, padded with zeros.
+    /// Strings longer than the field are truncated.
+    ///
+    /// If the field already holds the same string, it is left untouched
+    /// to keep whatever the M8 wrote after the string terminator.
     pub fn write_string(&mut self, str: &str, fill: usize) {
+        let current = self.buffer.get(self.pos..self.pos + fill);
+        if current.is_some_and(|field| decode_string(field) == str) {
+            self.skip(fill);
+            return;
+        }
+
         let bytes = str.as_bytes();
+        let bytes = &bytes[.. bytes.len().min(fill)];
         self.write_bytes(bytes);
         self.fill_till(0, fill - bytes.len());
     }